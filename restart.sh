@@ -62,7 +62,11 @@ dfx ledger balance --ledger-canister-id "$LEDGER_ID" && log "✅ Balance check s
 
 # Test bounty system
 log "Testing bounty system..."
-dfx canister call "$CANISTER_BACKEND" get_bounty_balance '(6)' && log "✅ Bounty balance test successful"
+if dfx canister call "$CANISTER_BACKEND" get_bounty_balance '(6)' | grep -q "Ok"; then
+  log "✅ Bounty balance test successful"
+else
+  log "⚠️  No bounty balance for artwork 6 (returns Err until it has a bounty)"
+fi
 
 # Create helpful aliases for testing
 log "Creating helpful aliases..."
@@ -263,7 +267,7 @@ dfx ledger transfer --ledger-canister-id bkyz2-fmaaa-aaaaa-qaaaq-cai     --amoun
 
 # # Check balance ✅
 # dfx canister call opencritique_backend get_bounty_balance '(1)'
-# # Result: "Balance: 1 ICP (100000000 e8s)"
+# # Result: (variant { Ok = record { e8s = 100_000_000 : nat64; token = record { ... }; account = record { ... } } })
 
 # # Transfer bounty to critic ✅
 # dfx canister call opencritique_backend transfer_bounty_to_critic "(1, principal \"mu7ba-6vje6-...\", 50000000)"
@@ -275,5 +279,5 @@ dfx ledger transfer --ledger-canister-id bkyz2-fmaaa-aaaaa-qaaaq-cai     --amoun
 
 # # Verify remaining escrow balance ✅
 # dfx canister call opencritique_backend get_bounty_balance '(1)'  
-# # Result: "Balance: 0.4999 ICP (49990000 e8s)" (after fee deduction)
+# # Result: (variant { Ok = record { e8s = 49_990_000 : nat64; ... } }) (after fee deduction)
 
@@ -0,0 +1,162 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Anonymous content that an admin can de-anonymize
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub enum AnonymousTarget {
+    Artwork(u64),
+    Critique { art_id: u64, critique_id: u64 },
+}
+
+/// Logged record of an admin revealing who is behind a pseudonym
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ModerationAction {
+    pub id: u64,
+    pub admin: Principal,
+    pub target: AnonymousTarget,
+    pub reason: String,
+    pub pseudonym: String,
+    pub revealed: Principal,
+    pub timestamp: u64,
+}
+
+thread_local! {
+    // Real principal -> stable pseudonym. Never exposed by public queries.
+    static PSEUDONYMS: RefCell<HashMap<Principal, String>> = RefCell::new(HashMap::new());
+    static PSEUDONYM_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    static MODERATION_LOG: RefCell<Vec<ModerationAction>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Upgrade snapshot of pseudonyms and the moderation log
+#[derive(CandidType, Deserialize)]
+pub(crate) struct AnonymityState {
+    pseudonyms: HashMap<Principal, String>,
+    pseudonym_counter: u64,
+    moderation_log: Vec<ModerationAction>,
+}
+
+pub(crate) fn save_state() -> AnonymityState {
+    AnonymityState {
+        pseudonyms: PSEUDONYMS.with(|s| s.take()),
+        pseudonym_counter: PSEUDONYM_COUNTER.with(|s| s.take()),
+        moderation_log: MODERATION_LOG.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: AnonymityState) {
+    PSEUDONYMS.with(|s| *s.borrow_mut() = state.pseudonyms);
+    PSEUDONYM_COUNTER.with(|s| *s.borrow_mut() = state.pseudonym_counter);
+    MODERATION_LOG.with(|s| *s.borrow_mut() = state.moderation_log);
+}
+
+/// Stable pseudonym for a user, assigned on first anonymous post
+pub(crate) fn pseudonym_of(user: Principal) -> String {
+    PSEUDONYMS.with(|p| {
+        p.borrow_mut()
+            .entry(user)
+            .or_insert_with(|| {
+                let n = PSEUDONYM_COUNTER.with(|c| {
+                    let mut c = c.borrow_mut();
+                    *c += 1;
+                    *c
+                });
+                format!("anon-{:05}", n)
+            })
+            .clone()
+    })
+}
+
+/// Post a critique that is shown under the caller's pseudonym. Bounty payouts
+/// and reputation still go to the real principal.
+#[update]
+pub fn post_critique_anonymously(art_id: u64, text: String) {
+    crate::add_critique(art_id, text, true);
+}
+
+/// Toggle anonymity of an artwork (author only)
+#[update]
+pub fn set_artwork_anonymous(art_id: u64, anonymous: bool) -> Result<String, String> {
+    let caller_principal = caller();
+
+    crate::ARTWORKS.with(|arts| {
+        let mut artworks = arts.borrow_mut();
+        let artwork = artworks
+            .iter_mut()
+            .find(|a| a.id == art_id)
+            .ok_or_else(|| "Artwork not found".to_string())?;
+
+        if artwork.author != caller_principal {
+            return Err("Only the artwork author can change its anonymity".to_string());
+        }
+        // Critique requests show their author
+        if anonymous && crate::critique_requests::is_attached(art_id) {
+            return Err("Artworks attached to a critique request cannot be anonymous".to_string());
+        }
+
+        artwork.anonymous = anonymous;
+        if anonymous {
+            crate::feed::retract_artist_items(art_id);
+            Ok(format!("Artwork {} is now shown as {}", art_id, pseudonym_of(caller_principal)))
+        } else {
+            Ok(format!("Artwork {} is now shown under your name", art_id))
+        }
+    })
+}
+
+/// Reveal the real principal behind anonymous content (admin only). Every
+/// call is recorded in the moderation log.
+#[update]
+pub fn reveal_anonymous_author(target: AnonymousTarget, reason: String) -> Result<Principal, String> {
+    let admin = caller();
+    if !crate::is_admin(&admin) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if reason.trim().is_empty() {
+        return Err("A moderation reason is required".to_string());
+    }
+
+    let revealed = crate::ARTWORKS.with(|arts| {
+        let artworks = arts.borrow();
+        match &target {
+            AnonymousTarget::Artwork(art_id) => artworks
+                .iter()
+                .find(|a| a.id == *art_id && a.anonymous)
+                .map(|a| a.author),
+            AnonymousTarget::Critique { art_id, critique_id } => artworks
+                .iter()
+                .find(|a| a.id == *art_id)
+                .and_then(|a| a.critiques.iter().find(|c| c.id == *critique_id && c.anonymous))
+                .map(|c| c.critic),
+        }
+    });
+    let revealed = revealed.ok_or_else(|| "Anonymous content not found".to_string())?;
+
+    MODERATION_LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        let action = ModerationAction {
+            id: log.len() as u64 + 1,
+            admin,
+            target,
+            reason,
+            pseudonym: pseudonym_of(revealed),
+            revealed,
+            timestamp: time(),
+        };
+        log.push(action);
+    });
+
+    Ok(revealed)
+}
+
+/// Log of de-anonymization actions (admin only)
+#[query]
+pub fn get_moderation_log() -> Result<Vec<ModerationAction>, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    Ok(MODERATION_LOG.with(|log| log.borrow().clone()))
+}
@@ -1,32 +1,305 @@
-use ic_cdk::api::{caller, time};
+use crate::runtime::{caller, time};
+use ic_cdk::api::call::CallResult;
 use ic_cdk::{update, query};
 use serde::{Deserialize, Serialize};
 use candid::{CandidType, Principal};
 use ic_ledger_types::*;
+pub use icrc_ledger_types::icrc1::account::Account;
+use icrc_ledger_types::{icrc1, icrc2};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::time::Duration;
 
-/* for testing purpose */
-const LOCAL_TESTING: bool = false; // Set to false for production
+use crate::config::{config, ledger_canister_id};
+#[cfg(feature = "mock-ledger")]
+use crate::config::is_testing;
 
-const ICP_FEE: u64 = 10_000; // 0.0001 ICP in e8s
+/// Lifecycle of a bounty escrow
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, Default)]
+pub enum BountyStatus {
+    #[default]
+    Prepared,      // escrow account created, funding not confirmed yet
+    Funded,        // escrow balance confirmed on the ledger
+    PartiallyPaid, // some critics paid, funds remain in escrow
+    Paid,          // bounty fully paid out
+    Expired,       // deadline passed before the bounty was fully paid
+    Refunded,      // remaining escrow returned to the author
+    Cancelled,     // withdrawn by the author before it was funded
+}
+
+impl BountyStatus {
+    fn can_transition_to(self, next: BountyStatus) -> bool {
+        use BountyStatus::*;
+        matches!(
+            (self, next),
+            (Prepared, Funded) | (Prepared, Expired) | (Prepared, Cancelled)
+                | (Funded, PartiallyPaid) | (Funded, Paid) | (Funded, Expired) | (Funded, Refunded)
+                | (PartiallyPaid, PartiallyPaid) | (PartiallyPaid, Paid) | (PartiallyPaid, Expired)
+                | (Expired, PartiallyPaid) | (Expired, Paid) | (Expired, Refunded)
+                | (Paid, Refunded)
+        )
+    }
+
+    /// Critics can only be paid from a confirmed, not yet settled escrow
+    pub fn is_payable(self) -> bool {
+        matches!(self, BountyStatus::Funded | BountyStatus::PartiallyPaid)
+    }
+
+    /// No further payouts or refunds are possible
+    pub fn is_settled(self) -> bool {
+        matches!(self, BountyStatus::Refunded | BountyStatus::Cancelled)
+    }
+}
+
+/// One entry of a bounty's status history
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyTransition {
+    pub from: BountyStatus,
+    pub to: BountyStatus,
+    pub timestamp: u64,
+    pub actor: Principal,
+    pub amount: Option<u64>,      // e8s moved by this transition, if any
+    pub block_index: Option<u64>, // ledger block of the transfer, if any
+    pub note: String,
+}
+
+/// Opt-in policy that pays the most upvoted critiques when the bounty expires,
+/// so the escrow cannot simply be reclaimed after collecting feedback
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct AutoDistribution {
+    /// Percent of the distributable escrow for the 1st, 2nd, ... ranked critique
+    pub split: Vec<u8>,
+    pub executed_at: Option<u64>,
+}
 
-// ✅ FIXED - Update this with your actual local ledger canister ID
-fn ledger_canister_id() -> Principal {
-    // Update this to match your actual ledger ID
-    // using dfx canister id icp_ledger_canister
-    Principal::from_text("bkyz2-fmaaa-aaaaa-qaaaq-cai") 
-        .expect("Invalid ledger canister ID")
+/// What an escrow transfer pays for, applied to the bounty once it completes
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub enum TransferKind {
+    CriticPayout { critique_id: Option<u64> },
+    Refund,
+    SponsorRefund, // a sponsor's pro rata share, returned before the author's refund
+    RequestPayout { artwork_id: u64, critique_id: u64 }, // award from a critique request escrow
+    PlatformFee, // the platform's cut of a settled payout, sent to the treasury
+}
+
+/// A transfer into a bounty escrow, seen by the ledger scanner or made through
+/// icrc2_transfer_from
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyDeposit {
+    pub block_index: u64,
+    pub from: Option<String>, // sender's account identifier (hex); None for mints
+    pub amount: u64,
+    pub timestamp: u64, // block time
+}
+
+/// Funds a third party added to a bounty, summed per contributor
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyContribution {
+    pub contributor: Principal,
+    pub amount: u64, // total contributed, in the token's smallest unit
+    pub contributed_at: u64, // time of the latest top-up
+    pub last_block_index: Option<u64>,
+    pub refunded: u64, // share returned when the bounty was withdrawn, after the ledger fee
+    pub refunded_at: Option<u64>,
+    pub refund_block: Option<u64>,
+}
+
+/// Escrow transfer recorded before the ledger call. It stays here while the
+/// outcome is unknown and is retried with the same memo and created_at_time,
+/// so the ledger deduplicates it instead of paying twice.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct PendingTransfer {
+    pub id: u64, // per-bounty sequence number, part of the memo
+    pub kind: TransferKind,
+    pub to: Principal,
+    pub amount: u64,
+    pub fee: u64,
+    pub created_at_time: u64,
+    pub note: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub platform_fee: u64, // withheld from `amount` for the treasury, queued once this payout settles
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 pub struct Bounty {
-    pub ledger: Principal,
+    pub ledger: Principal, // ICRC-1 ledger of the bounty's token; amounts are in its smallest unit
     pub subaccount: Option<Subaccount>,
-    pub intended_amount: u64, // in e8s (1 ICP = 100_000_000 e8s)
+    pub intended_amount: u64, // in the token's smallest unit (1 ICP = 100_000_000 e8s)
     pub actual_amount: u64,   // actual amount received
-    pub released: bool,
+    #[serde(default)]
+    pub status: BountyStatus,
     pub created_at: u64,
     pub expires_at: Option<u64>, // optional expiration timestamp
-    pub recipient: Option<Principal>, // who can claim this bounty
+    pub recipient: Option<Principal>, // last critic paid from this bounty
+    #[serde(default)]
+    pub funded_at: Option<u64>, // set once the escrow balance is confirmed on the ledger
+    #[serde(default)]
+    pub paid_amount: u64, // total e8s paid out to critics
+    #[serde(default)]
+    pub fees_paid: u64, // ledger fees paid from the escrow
+    #[serde(default)]
+    pub platform_fees: u64, // platform's cut of payouts, sent to the treasury
+    #[serde(default)]
+    pub transitions: Vec<BountyTransition>,
+    #[serde(default)]
+    pub auto_distribution: Option<AutoDistribution>,
+    #[serde(default)]
+    pub reminder_sent_at: Option<u64>, // expiry reminder already sent to the author
+    #[serde(default)]
+    pub pending_transfers: Vec<PendingTransfer>,
+    #[serde(default)]
+    pub next_transfer_id: u64,
+    #[serde(default)]
+    pub disputes: Vec<crate::disputes::BountyDispute>,
+    #[serde(default)]
+    pub contributions: Vec<BountyContribution>, // third-party sponsors; the author's own funds are the rest
+    #[serde(default)]
+    pub deposits: Vec<BountyDeposit>,
+}
+
+impl Bounty {
+    /// e8s still held in escrow according to internal records, not counting
+    /// transfers that may already have left or the platform fees they will queue
+    pub fn remaining(&self) -> u64 {
+        let pending: u64 = self.pending_transfers.iter().map(|t| t.amount + t.fee + t.platform_fee).sum();
+        let refunded: u64 = self.contributions.iter().map(|c| c.refunded).sum();
+        self.actual_amount.saturating_sub(self.paid_amount + self.fees_paid + self.platform_fees + pending + refunded)
+    }
+
+    /// Total funds added by sponsors other than the author
+    pub fn sponsored_amount(&self) -> u64 {
+        self.contributions.iter().map(|c| c.amount).sum()
+    }
+
+    /// A critic's dispute is waiting for a ruling or for its upheld payout, so
+    /// the escrow must stay put
+    pub fn has_open_dispute(&self) -> bool {
+        self.disputes.iter().any(|d| d.is_unresolved())
+    }
+
+    /// A dispute over `critique_id` may still pay the critic, so the critique
+    /// cannot be paid any other way
+    pub fn is_disputed(&self, critique_id: u64) -> bool {
+        self.disputes.iter().any(|d| d.critique_id == critique_id && d.is_unresolved())
+    }
+
+    /// A transfer paying for `critique_id` has not settled yet
+    pub(crate) fn has_pending_payout(&self, critique_id: u64) -> bool {
+        self.pending_transfers.iter().any(|t| {
+            matches!(t.kind, TransferKind::CriticPayout { critique_id: Some(id) } if id == critique_id)
+        })
+    }
+
+    /// Transfer fee of the bounty's ledger
+    pub fn fee(&self) -> u64 {
+        crate::tokens::token_fee(self.ledger)
+    }
+
+    /// Check that `amount` plus its ledger fee fits in the escrow once the
+    /// `reserved` funds are held back
+    pub(crate) fn check_payable(&self, amount: u64, reserved: u64) -> Result<(), BountyError> {
+        let needed = amount.checked_add(self.fee()).ok_or(BountyError::InvalidAmount)?;
+        if needed > self.remaining().saturating_sub(reserved) {
+            return Err(BountyError::InsufficientFunds);
+        }
+        Ok(())
+    }
+
+    /// Move to `to` if the transition is valid, recording it in the log
+    pub(crate) fn transition(
+        &mut self,
+        to: BountyStatus,
+        amount: Option<u64>,
+        block_index: Option<u64>,
+        note: &str,
+    ) -> Result<(), BountyError> {
+        if !self.status.can_transition_to(to) {
+            return Err(BountyError::InvalidTransition(format!("{:?} -> {:?}", self.status, to)));
+        }
+        self.transitions.push(BountyTransition {
+            from: self.status,
+            to,
+            timestamp: time(),
+            actor: caller(),
+            amount,
+            block_index,
+            note: note.to_string(),
+        });
+        self.status = to;
+        Ok(())
+    }
+
+    /// Record a deposit found on the ledger and count it toward the bounty,
+    /// unless it is already known. Returns whether it was new.
+    pub(crate) fn record_deposit(&mut self, deposit: BountyDeposit) -> bool {
+        if self.has_deposit(deposit.block_index) {
+            return false;
+        }
+        self.deposits.push(deposit.clone());
+        if self.status.is_settled() {
+            // Nothing can be paid from it any more; left to the escrow audit
+            return true;
+        }
+
+        match self.funded_at {
+            // Until funding is confirmed every deposit counts toward it. The
+            // recorded amount may already include earlier unscanned deposits.
+            None => {
+                let total: u64 = self.deposits.iter().map(|d| d.amount).sum();
+                self.actual_amount = self.actual_amount.max(total);
+                if self.status == BountyStatus::Prepared && self.actual_amount >= self.intended_amount {
+                    self.funded_at = Some(time());
+                    let _ = self.transition(
+                        BountyStatus::Funded,
+                        Some(self.actual_amount),
+                        Some(deposit.block_index),
+                        "deposit found on the ledger",
+                    );
+                }
+            }
+            // A balance confirmed after the deposit already includes it
+            Some(funded_at) if deposit.timestamp > funded_at => {
+                self.actual_amount += deposit.amount;
+                self.intended_amount += deposit.amount;
+            }
+            Some(_) => {}
+        }
+        true
+    }
+
+    /// The deposit in ledger block `block_index` is already recorded, e.g. by
+    /// the deposit scanner while an icrc2_transfer_from reply was pending
+    pub(crate) fn has_deposit(&self, block_index: u64) -> bool {
+        self.deposits.iter().any(|d| d.block_index == block_index)
+    }
+
+    /// End an open bounty now, as if its deadline had passed
+    pub(crate) fn close(&mut self, note: &str) {
+        let open = matches!(
+            self.status,
+            BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid
+        );
+        if open {
+            self.expires_at = Some(time());
+            let _ = self.transition(BountyStatus::Expired, None, None, note);
+        }
+    }
+
+    /// Mark the bounty expired if its deadline has passed while still open
+    pub(crate) fn expire_if_due(&mut self) {
+        let open = matches!(
+            self.status,
+            BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid
+        );
+        if let Some(expires_at) = self.expires_at {
+            if open && time() > expires_at {
+                let _ = self.transition(BountyStatus::Expired, None, None, "deadline passed");
+            }
+        }
+    }
 }
 
 impl Default for Bounty {
@@ -36,10 +309,99 @@ impl Default for Bounty {
             subaccount: None,
             intended_amount: 0,
             actual_amount: 0,
-            released: false,
+            status: BountyStatus::Prepared,
             created_at: time(),
             expires_at: None,
             recipient: None,
+            funded_at: None,
+            paid_amount: 0,
+            fees_paid: 0,
+            platform_fees: 0,
+            transitions: vec![],
+            auto_distribution: None,
+            reminder_sent_at: None,
+            pending_transfers: vec![],
+            next_transfer_id: 0,
+            disputes: vec![],
+            contributions: vec![],
+            deposits: vec![],
+        }
+    }
+}
+
+/// Bounty as returned to any caller. Principals of anonymous authors and
+/// critics are replaced with the anonymous principal, and their funding
+/// accounts are left out.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyView {
+    pub ledger: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub intended_amount: u64,
+    pub actual_amount: u64,
+    pub status: BountyStatus,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub recipient: Option<Principal>,
+    pub funded_at: Option<u64>,
+    pub paid_amount: u64,
+    pub fees_paid: u64,
+    pub platform_fees: u64,
+    pub transitions: Vec<BountyTransition>,
+    pub auto_distribution: Option<AutoDistribution>,
+    pub pending_transfers: Vec<PendingTransfer>,
+    pub disputes: Vec<crate::disputes::BountyDispute>,
+    pub contributions: Vec<BountyContribution>,
+    pub deposits: Vec<BountyDeposit>,
+}
+
+impl BountyView {
+    /// Copy of `bounty` that does not reveal the principals in `hidden`. With
+    /// `hide_depositors` no deposit sender is shown, e.g. for anonymous works.
+    pub(crate) fn redacted(bounty: &Bounty, hidden: &HashSet<Principal>, hide_depositors: bool) -> Self {
+        let hide = |p: Principal| if hidden.contains(&p) { Principal::anonymous() } else { p };
+        let hidden_accounts: HashSet<String> = hidden
+            .iter()
+            .map(|p| AccountIdentifier::new(p, &DEFAULT_SUBACCOUNT).to_hex())
+            .collect();
+        // Older payout notes name the critic
+        let scrub = |note: &str| hidden
+            .iter()
+            .fold(note.to_string(), |note, p| note.replace(&p.to_text(), "anonymous"));
+
+        Self {
+            ledger: bounty.ledger,
+            subaccount: bounty.subaccount,
+            intended_amount: bounty.intended_amount,
+            actual_amount: bounty.actual_amount,
+            status: bounty.status,
+            created_at: bounty.created_at,
+            expires_at: bounty.expires_at,
+            recipient: bounty.recipient.map(hide),
+            funded_at: bounty.funded_at,
+            paid_amount: bounty.paid_amount,
+            fees_paid: bounty.fees_paid,
+            platform_fees: bounty.platform_fees,
+            transitions: bounty.transitions
+                .iter()
+                .map(|t| BountyTransition { actor: hide(t.actor), note: scrub(&t.note), ..t.clone() })
+                .collect(),
+            auto_distribution: bounty.auto_distribution.clone(),
+            pending_transfers: bounty.pending_transfers
+                .iter()
+                .map(|t| PendingTransfer { to: hide(t.to), note: scrub(&t.note), ..t.clone() })
+                .collect(),
+            disputes: bounty.disputes.iter().map(|d| d.redacted(hidden)).collect(),
+            contributions: bounty.contributions
+                .iter()
+                .map(|c| BountyContribution { contributor: hide(c.contributor), ..c.clone() })
+                .collect(),
+            deposits: bounty.deposits
+                .iter()
+                .map(|d| BountyDeposit {
+                    from: d.from.clone().filter(|from| !hide_depositors && !hidden_accounts.contains(from)),
+                    ..d.clone()
+                })
+                .collect(),
         }
     }
 }
@@ -51,6 +413,36 @@ pub struct BountyTransferRequest {
     pub amount: u64, // amount to transfer in e8s
 }
 
+/// How much of a bounty one critique receives
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum AwardShare {
+    Amount(u64), // fixed amount in e8s
+    Percent(u8), // share of the escrow left after ledger fees
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CritiqueAward {
+    pub critique_id: u64,
+    pub share: AwardShare,
+}
+
+/// Bounty payout recorded on a rewarded critique
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CritiqueReward {
+    pub amount: u64,
+    pub block_index: Option<u64>,
+    pub paid_at: u64,
+}
+
+/// Outcome of one award made by `award_bounty`
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct AwardPayout {
+    pub critique_id: u64,
+    pub amount: u64,
+    pub block_index: Option<u64>,
+    pub error: Option<String>, // set when this transfer failed
+}
+
 #[derive(CandidType, Serialize, Debug)]
 pub enum BountyError {
     NotFound,
@@ -61,6 +453,11 @@ pub enum BountyError {
     InvalidAmount,
     Expired,
     NotReady,
+    NotFunded,
+    InvalidTransition(String),
+    Locked,                  // another operation on this bounty is in progress
+    TransferPending(String), // outcome unknown, see reconcile_bounty_transfers
+    UnsupportedToken,        // the ledger is not a registered, enabled token
 }
 
 #[derive(CandidType, Serialize, Debug)]
@@ -69,8 +466,44 @@ pub enum BountyResult {
     Error(BountyError),
 }
 
+// Domain separator for escrow subaccounts, so they cannot coincide with
+// subaccounts derived by any other scheme
+const ESCROW_SUBACCOUNT_DOMAIN: &[u8] = b"opencritique/bounty-escrow/v1";
+
+// Same scheme for the escrows of critique requests, in their own domain
+const REQUEST_SUBACCOUNT_DOMAIN: &[u8] = b"opencritique/critique-request-escrow/v1";
+
+// The platform treasury, which collects fees on payouts and NFT sales
+const TREASURY_SUBACCOUNT_DOMAIN: &[u8] = b"opencritique/treasury/v1";
+
+// SHA-256(len(domain) || domain || id || len(author) || author)
+fn hashed_subaccount(domain: &[u8], id: u64, author: Principal) -> Subaccount {
+    let author_bytes = author.as_slice();
+    let mut hasher = Sha256::new();
+    hasher.update([domain.len() as u8]);
+    hasher.update(domain);
+    hasher.update(id.to_be_bytes());
+    hasher.update([author_bytes.len() as u8]);
+    hasher.update(author_bytes);
+    Subaccount(hasher.finalize().into())
+}
+
 // Generate a unique subaccount for each artwork's bounty
 fn generate_bounty_subaccount(artwork_id: u64, author: Principal) -> Subaccount {
+    hashed_subaccount(ESCROW_SUBACCOUNT_DOMAIN, artwork_id, author)
+}
+
+pub(crate) fn request_subaccount(request_id: u64, author: Principal) -> Subaccount {
+    hashed_subaccount(REQUEST_SUBACCOUNT_DOMAIN, request_id, author)
+}
+
+pub(crate) fn treasury_subaccount() -> Subaccount {
+    hashed_subaccount(TREASURY_SUBACCOUNT_DOMAIN, 0, crate::runtime::id())
+}
+
+// Pre-hash derivation (artwork id + author truncated to 24 bytes). Escrows
+// created with it keep using it; see migrate_escrow_subaccounts.
+fn legacy_bounty_subaccount(artwork_id: u64, author: Principal) -> Subaccount {
     let mut subaccount = [0u8; 32];
     let artwork_bytes = artwork_id.to_be_bytes();
     let author_bytes = author.as_slice();
@@ -83,10 +516,144 @@ fn generate_bounty_subaccount(artwork_id: u64, author: Principal) -> Subaccount
     Subaccount(subaccount)
 }
 
+// Subaccount of an artwork's escrow: the one recorded on its bounty if the
+// artwork exists with that author, otherwise a freshly derived one
+fn escrow_subaccount(artwork_id: u64, author: Principal) -> Subaccount {
+    crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id && a.author == author)
+            .and_then(|a| a.bounty.as_ref())
+            .map(|b| stored_subaccount(artwork_id, author, b))
+    })
+    .unwrap_or_else(|| generate_bounty_subaccount(artwork_id, author))
+}
+
+// Bounties without a recorded subaccount predate the hashed scheme
+pub(crate) fn stored_subaccount(artwork_id: u64, author: Principal, bounty: &Bounty) -> Subaccount {
+    bounty.subaccount.unwrap_or_else(|| legacy_bounty_subaccount(artwork_id, author))
+}
+
 // Get the account identifier for the bounty escrow
 fn get_bounty_account_identifier(artwork_id: u64, author: Principal) -> AccountIdentifier {
-    let subaccount = generate_bounty_subaccount(artwork_id, author);
-    AccountIdentifier::new(&ic_cdk::id(), &subaccount)
+    AccountIdentifier::new(&crate::runtime::id(), &escrow_subaccount(artwork_id, author))
+}
+
+// ICRC-1 escrow account (this canister + bounty subaccount). On the ICP ledger it
+// maps to the same funds as the legacy AccountIdentifier of that subaccount.
+pub(crate) fn escrow_icrc_account(subaccount: Subaccount) -> Account {
+    Account {
+        owner: crate::runtime::id(),
+        subaccount: Some(subaccount.0),
+    }
+}
+
+// Escrow account of an existing bounty, using the subaccount recorded on it
+pub(crate) fn bounty_escrow_account(artwork_id: u64, author: Principal, bounty: &Bounty) -> Account {
+    escrow_icrc_account(stored_subaccount(artwork_id, author, bounty))
+}
+
+fn nat_to_u64(n: candid::Nat) -> Result<u64, BountyError> {
+    u64::try_from(n.0).map_err(|_| BountyError::TransferFailed("Amount exceeds u64".to_string()))
+}
+
+// Ledger calls. Test builds send them to the built-in mock ledger in the Testing environment.
+async fn icrc1_balance_of(ledger: Principal, account: Account) -> CallResult<(candid::Nat,)> {
+    #[cfg(feature = "mock-ledger")]
+    if is_testing() {
+        return crate::mock_ledger::icrc1_balance_of(ledger, account);
+    }
+    ic_cdk::call(ledger, "icrc1_balance_of", (account,)).await
+}
+
+async fn icrc1_transfer(ledger: Principal, arg: icrc1::transfer::TransferArg) -> CallResult<(Result<candid::Nat, icrc1::transfer::TransferError>,)> {
+    #[cfg(feature = "mock-ledger")]
+    if is_testing() {
+        return crate::mock_ledger::icrc1_transfer(ledger, crate::runtime::id(), arg);
+    }
+    ic_cdk::call(ledger, "icrc1_transfer", (arg,)).await
+}
+
+async fn icrc2_transfer_from(
+    ledger: Principal,
+    args: icrc2::transfer_from::TransferFromArgs,
+) -> CallResult<(Result<candid::Nat, icrc2::transfer_from::TransferFromError>,)> {
+    #[cfg(feature = "mock-ledger")]
+    if is_testing() {
+        return crate::mock_ledger::icrc2_transfer_from(ledger, crate::runtime::id(), args);
+    }
+    ic_cdk::call(ledger, "icrc2_transfer_from", (args,)).await
+}
+
+// Query the ledger for the balance (in e8s) of an escrow account
+pub(crate) async fn ledger_balance(ledger: Principal, account: Account) -> Result<u64, BountyError> {
+    match icrc1_balance_of(ledger, account).await {
+        Ok((balance,)) => nat_to_u64(balance),
+        Err((code, msg)) => Err(BountyError::TransferFailed(format!("Balance check failed: {}: {}", code as u8, msg))),
+    }
+}
+
+/// Owner of a bounty escrow: an artwork, or a critique request whose escrow
+/// is shared by the artworks attached to it. Artwork ids convert implicitly.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum BountyKey {
+    Artwork(u64),
+    Request(u64),
+}
+
+impl BountyKey {
+    /// Artwork that notifications about this escrow link to
+    pub(crate) fn artwork_id(self) -> Option<u64> {
+        match self {
+            BountyKey::Artwork(artwork_id) => Some(artwork_id),
+            BountyKey::Request(_) => None,
+        }
+    }
+}
+
+impl From<u64> for BountyKey {
+    fn from(artwork_id: u64) -> Self {
+        BountyKey::Artwork(artwork_id)
+    }
+}
+
+// Apply a change to a bounty, if it exists
+pub(crate) fn with_bounty_mut<R>(key: impl Into<BountyKey>, f: impl FnOnce(&mut Bounty) -> R) -> Option<R> {
+    match key.into() {
+        BountyKey::Artwork(artwork_id) => crate::ARTWORKS.with(|artworks| {
+            artworks.borrow_mut()
+                .iter_mut()
+                .find(|a| a.id == artwork_id)
+                .and_then(|a| a.bounty.as_mut())
+                .map(f)
+        }),
+        BountyKey::Request(request_id) => crate::critique_requests::with_request_bounty_mut(request_id, f),
+    }
+}
+
+thread_local! {
+    static BOUNTY_LOCKS: RefCell<HashSet<BountyKey>> = RefCell::new(HashSet::new());
+}
+
+/// Exclusive access to one bounty across awaits. Released on drop, which also
+/// happens when a callback traps and the call context is cleaned up.
+pub(crate) struct BountyLock(BountyKey);
+
+impl BountyLock {
+    pub(crate) fn acquire(key: impl Into<BountyKey>) -> Result<Self, BountyError> {
+        let key = key.into();
+        if BOUNTY_LOCKS.with(|l| l.borrow_mut().insert(key)) {
+            Ok(BountyLock(key))
+        } else {
+            Err(BountyError::Locked)
+        }
+    }
+}
+
+impl Drop for BountyLock {
+    fn drop(&mut self) {
+        BOUNTY_LOCKS.with(|l| l.borrow_mut().remove(&self.0));
+    }
 }
 
 /// Prepare a bounty for an artwork (called during upload process)
@@ -105,10 +672,22 @@ pub async fn prepare_bounty(artwork_id: u64, intended_amount: u64) -> BountyResu
         subaccount: Some(subaccount),
         intended_amount,
         actual_amount: 0,
-        released: false,
+        status: BountyStatus::Prepared,
         created_at: time(),
-        expires_at: Some(time() + 30 * 24 * 60 * 60 * 1_000_000_000), // 30 days in nanoseconds
+        expires_at: Some(time() + config().bounty_duration_ns),
         recipient: None,
+        funded_at: None,
+        paid_amount: 0,
+        fees_paid: 0,
+        platform_fees: 0,
+        transitions: vec![],
+        auto_distribution: None,
+        reminder_sent_at: None,
+        pending_transfers: vec![],
+        next_transfer_id: 0,
+        disputes: vec![],
+        contributions: vec![],
+        deposits: vec![],
     };
 
     // Note: The actual transfer happens on the frontend using Plug wallet
@@ -128,6 +707,11 @@ pub async fn transfer_bounty_to_critic(
     amount: u64,
 ) -> BountyResult {
     let caller_principal = caller();
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
     
     // Get artwork and verify caller is the author
     let artwork = crate::ARTWORKS.with(|artworks| {
@@ -151,126 +735,965 @@ pub async fn transfer_bounty_to_critic(
         None => return BountyResult::Error(BountyError::NotFound),
     };
 
-    if bounty.released {
-        return BountyResult::Error(BountyError::AlreadyReleased);
+    match bounty.status {
+        BountyStatus::Prepared => return BountyResult::Error(BountyError::NotFunded),
+        BountyStatus::Expired => return BountyResult::Error(BountyError::Expired),
+        status if !status.is_payable() => return BountyResult::Error(BountyError::AlreadyReleased),
+        _ => {}
+    }
+
+    if amount == 0 {
+        return BountyResult::Error(BountyError::InvalidAmount);
+    }
+    let reserved = reserved_for_critics(artwork_id, bounty, None);
+    if let Err(e) = bounty.check_payable(amount, reserved) {
+        return BountyResult::Error(e);
+    }
+
+    // Perform the transfer
+    let kind = TransferKind::CriticPayout { critique_id: None };
+    match send_from_escrow(artwork_id, kind, critic_principal, amount, "paid critic").await {
+        Ok(block_index) => BountyResult::Success(format!(
+            "Successfully transferred {} to critic {}. {}",
+            crate::tokens::format_amount(bounty.ledger, amount),
+            critic_principal.to_text(),
+            block_note(block_index)
+        )),
+        Err(e) => BountyResult::Error(e),
+    }
+}
+
+pub(crate) fn block_note(block_index: u64) -> String {
+    format!("Block index: {}", block_index)
+}
+
+// Ledger result of an escrow transfer
+pub(crate) enum TransferFailure {
+    Rejected(BountyError), // the ledger did not execute it
+    Unknown(String),       // it may or may not have been executed
+}
+
+// Deterministic memo of an escrow transfer, so retries are recognised as duplicates
+fn transfer_memo(key: BountyKey, transfer_id: u64) -> icrc1::transfer::Memo {
+    let (BountyKey::Artwork(id) | BountyKey::Request(id)) = key;
+    let mut memo = id.to_be_bytes().to_vec();
+    memo.extend_from_slice(&transfer_id.to_be_bytes());
+    icrc1::transfer::Memo(serde_bytes::ByteBuf::from(memo))
+}
+
+// Transfer from a subaccount of this canister to `to` (ICRC-1)
+pub(crate) async fn ledger_transfer(
+    ledger: Principal,
+    from_subaccount: Subaccount,
+    to: Account,
+    amount: u64,
+    fee: u64,
+    memo: icrc1::transfer::Memo,
+    created_at_time: u64,
+) -> Result<u64, TransferFailure> {
+    let transfer_args = icrc1::transfer::TransferArg {
+        from_subaccount: Some(from_subaccount.0),
+        to,
+        fee: Some(fee.into()),
+        created_at_time: Some(created_at_time),
+        memo: Some(memo),
+        amount: amount.into(),
+    };
+
+    match icrc1_transfer(ledger, transfer_args).await {
+        Ok((Ok(block_index),)) => nat_to_u64(block_index).map_err(TransferFailure::Rejected),
+        // An earlier attempt of this very transfer already went through
+        Ok((Err(icrc1::transfer::TransferError::Duplicate { duplicate_of }),)) => {
+            nat_to_u64(duplicate_of).map_err(TransferFailure::Rejected)
+        }
+        Ok((Err(icrc1::transfer::TransferError::TooOld),)) => Err(TransferFailure::Unknown(
+            "outside the ledger deduplication window; check the ledger and resolve manually".to_string(),
+        )),
+        Ok((Err(transfer_error),)) => Err(TransferFailure::Rejected(BountyError::TransferFailed(format!("{:?}", transfer_error)))),
+        Err((code, msg)) => Err(TransferFailure::Unknown(format!("Call failed: {}: {}", code as u8, msg))),
+    }
+}
+
+// Ledger and escrow subaccount of a bounty
+fn escrow_of(key: BountyKey) -> Option<(Principal, Subaccount)> {
+    match key {
+        BountyKey::Artwork(artwork_id) => crate::ARTWORKS.with(|artworks| {
+            artworks.borrow()
+                .iter()
+                .find(|a| a.id == artwork_id)
+                .and_then(|a| a.bounty.as_ref().map(|b| (b.ledger, stored_subaccount(a.id, a.author, b))))
+        }),
+        BountyKey::Request(request_id) => crate::critique_requests::request_escrow(request_id)
+            .map(|(ledger, _, subaccount)| (ledger, subaccount)),
+    }
+}
+
+// Send `amount` out of a bounty escrow. The transfer is recorded as pending
+// before the call and settled once the ledger confirms it. Callers must hold
+// the bounty lock.
+pub(crate) async fn send_from_escrow(
+    key: impl Into<BountyKey>,
+    kind: TransferKind,
+    to: Principal,
+    amount: u64,
+    note: &str,
+) -> Result<u64, BountyError> {
+    let key = key.into();
+    let (ledger, subaccount) = escrow_of(key).ok_or(BountyError::NotFound)?;
+    let transfer = with_bounty_mut(key, |bounty| {
+        let platform_fee = match kind {
+            TransferKind::CriticPayout { .. } | TransferKind::RequestPayout { .. } => {
+                crate::treasury::platform_cut(amount, bounty.fee())
+            }
+            _ => 0,
+        };
+        let transfer = PendingTransfer {
+            id: bounty.next_transfer_id,
+            kind,
+            to,
+            amount: amount - platform_fee,
+            fee: bounty.fee(),
+            created_at_time: time(),
+            note: note.to_string(),
+            attempts: 0,
+            last_error: None,
+            platform_fee,
+        };
+        bounty.next_transfer_id += 1;
+        bounty.pending_transfers.push(transfer.clone());
+        transfer
+    }).ok_or(BountyError::NotFound)?;
+
+    let block_index = attempt_transfer(key, ledger, subaccount, &transfer).await?;
+    if transfer.platform_fee > 0 {
+        send_platform_fees(key, ledger, subaccount).await;
+    }
+    Ok(block_index)
+}
+
+// Send the platform fees queued by settled payouts to the treasury. Failures,
+// rejected ones included, stay pending and are retried by the bounty sweep.
+async fn send_platform_fees(key: BountyKey, ledger: Principal, subaccount: Subaccount) {
+    let queued: Vec<PendingTransfer> = with_bounty_mut(key, |bounty| {
+        bounty.pending_transfers.iter().filter(|t| matches!(t.kind, TransferKind::PlatformFee)).cloned().collect()
+    }).unwrap_or_default();
+    for transfer in queued {
+        if let Err(e) = attempt_transfer(key, ledger, subaccount, &transfer).await {
+            ic_cdk::println!("Bounty {:?} platform fee {} not sent: {:?}", key, transfer.id, e);
+        }
+    }
+}
+
+// Run (or re-run) a pending transfer and settle, drop or keep it by outcome.
+// A rejected platform fee is owed to the treasury all the same, so it stays
+// queued as a new transfer.
+async fn attempt_transfer(key: BountyKey, ledger: Principal, subaccount: Subaccount, transfer: &PendingTransfer) -> Result<u64, BountyError> {
+    let to = match transfer.kind {
+        TransferKind::PlatformFee => escrow_icrc_account(treasury_subaccount()),
+        _ => Account { owner: transfer.to, subaccount: None },
+    };
+    let result = ledger_transfer(
+        ledger,
+        subaccount,
+        to,
+        transfer.amount,
+        transfer.fee,
+        transfer_memo(key, transfer.id),
+        transfer.created_at_time,
+    ).await;
+
+    match result {
+        Ok(block_index) => {
+            settle_transfer(key, transfer.id, Some(block_index));
+            Ok(block_index)
+        }
+        Err(TransferFailure::Rejected(e)) if matches!(transfer.kind, TransferKind::PlatformFee) => {
+            with_bounty_mut(key, |bounty| {
+                let fee = bounty.fee();
+                if let Some(t) = bounty.pending_transfers.iter_mut().find(|t| t.id == transfer.id) {
+                    t.amount = (t.amount + t.fee).saturating_sub(fee);
+                    t.fee = fee;
+                    t.created_at_time = time();
+                    t.attempts += 1;
+                    t.last_error = Some(format!("{:?}", e));
+                }
+            });
+            Err(e)
+        }
+        Err(TransferFailure::Rejected(e)) => {
+            with_bounty_mut(key, |bounty| bounty.pending_transfers.retain(|t| t.id != transfer.id));
+            Err(e)
+        }
+        Err(TransferFailure::Unknown(msg)) => {
+            with_bounty_mut(key, |bounty| {
+                if let Some(t) = bounty.pending_transfers.iter_mut().find(|t| t.id == transfer.id) {
+                    t.attempts += 1;
+                    t.last_error = Some(msg.clone());
+                }
+            });
+            Err(BountyError::TransferPending(msg))
+        }
+    }
+}
+
+// Remove a completed transfer from the pending list and book it on the bounty
+fn settle_transfer(key: BountyKey, transfer_id: u64, block_index: Option<u64>) {
+    let transfer = with_bounty_mut(key, |bounty| {
+        let index = bounty.pending_transfers.iter().position(|t| t.id == transfer_id)?;
+        Some(bounty.pending_transfers.remove(index))
+    }).flatten();
+    let Some(transfer) = transfer else { return };
+
+    // Queue the platform's cut first, so the payout below sees it as leaving the escrow
+    if transfer.platform_fee > 0 {
+        with_bounty_mut(key, |bounty| {
+            let fee = bounty.fee();
+            bounty.pending_transfers.push(PendingTransfer {
+                id: bounty.next_transfer_id,
+                kind: TransferKind::PlatformFee,
+                to: crate::runtime::id(),
+                amount: transfer.platform_fee.saturating_sub(fee),
+                fee,
+                created_at_time: time(),
+                note: format!("platform fee on transfer {}", transfer.id),
+                attempts: 0,
+                last_error: None,
+                platform_fee: 0,
+            });
+            bounty.next_transfer_id += 1;
+        });
+    }
+
+    let reward = CritiqueReward {
+        amount: transfer.amount,
+        block_index,
+        paid_at: time(),
+    };
+    match transfer.kind {
+        TransferKind::CriticPayout { critique_id } => {
+            record_critic_payout(key, transfer.to, transfer.amount, transfer.fee, block_index);
+            if let (Some(critique_id), Some(artwork_id)) = (critique_id, key.artwork_id()) {
+                record_critique_reward(artwork_id, critique_id, reward);
+                crate::bounty_claims::record_claim_payout(artwork_id, critique_id, block_index);
+            }
+        }
+        TransferKind::RequestPayout { artwork_id, critique_id } => {
+            record_critic_payout(key, transfer.to, transfer.amount, transfer.fee, block_index);
+            record_critique_reward(artwork_id, critique_id, reward);
+        }
+        TransferKind::SponsorRefund => {
+            with_bounty_mut(key, |bounty| {
+                bounty.fees_paid += transfer.fee;
+                if let Some(c) = bounty.contributions.iter_mut().find(|c| c.contributor == transfer.to) {
+                    c.refunded += transfer.amount;
+                    c.refunded_at = Some(time());
+                    c.refund_block = block_index;
+                }
+            });
+        }
+        TransferKind::Refund => {
+            with_bounty_mut(key, |bounty| {
+                bounty.fees_paid += transfer.fee;
+                if let Err(e) = bounty.transition(BountyStatus::Refunded, Some(transfer.amount), block_index, &transfer.note) {
+                    ic_cdk::println!("Bounty {:?} refund not logged: {:?}", key, e);
+                }
+            });
+        }
+        TransferKind::PlatformFee => {
+            let ledger = with_bounty_mut(key, |bounty| {
+                bounty.platform_fees += transfer.amount;
+                bounty.fees_paid += transfer.fee;
+                bounty.ledger
+            });
+            if let Some(ledger) = ledger {
+                crate::treasury::record_entry(ledger, crate::treasury::TreasurySource::Bounty(key), transfer.amount, block_index);
+            }
+        }
+    }
+}
+
+/// Retry escrow transfers whose outcome is unknown (author or admin). The
+/// ledger deduplicates transfers that already went through. Returns the
+/// transfers that are still pending.
+#[update]
+pub async fn reconcile_bounty_transfers(artwork_id: u64) -> Result<Vec<PendingTransfer>, BountyError> {
+    let author = crate::ARTWORKS.with(|artworks| {
+        artworks.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.author)
+    }).ok_or(BountyError::NotFound)?;
+    let caller_principal = caller();
+    if caller_principal != author && !crate::is_admin(&caller_principal) {
+        return Err(BountyError::NotAuthorized);
+    }
+    reconcile_pending_transfers(artwork_id).await
+}
+
+pub(crate) async fn reconcile_pending_transfers(key: impl Into<BountyKey>) -> Result<Vec<PendingTransfer>, BountyError> {
+    let key = key.into();
+    let _lock = BountyLock::acquire(key)?;
+    let (ledger, subaccount) = escrow_of(key).ok_or(BountyError::NotFound)?;
+    let pending = with_bounty_mut(key, |bounty| bounty.pending_transfers.clone()).unwrap_or_default();
+
+    for transfer in pending {
+        if let Err(e) = attempt_transfer(key, ledger, subaccount, &transfer).await {
+            ic_cdk::println!("Bounty {:?} transfer {} still unresolved: {:?}", key, transfer.id, e);
+        }
+    }
+    Ok(with_bounty_mut(key, |bounty| bounty.pending_transfers.clone()).unwrap_or_default())
+}
+
+/// Resolve a pending transfer of an artwork or critique request escrow by hand
+/// after checking the ledger (admin only): pass the block index if it was
+/// executed, or None to discard it.
+#[update]
+pub fn resolve_pending_transfer(key: BountyKey, transfer_id: u64, block_index: Option<u64>) -> BountyResult {
+    if !crate::is_admin(&caller()) {
+        return BountyResult::Error(BountyError::NotAuthorized);
+    }
+    let _lock = match BountyLock::acquire(key) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    let exists = with_bounty_mut(key, |bounty| bounty.pending_transfers.iter().any(|t| t.id == transfer_id));
+    if exists != Some(true) {
+        return BountyResult::Error(BountyError::NotFound);
+    }
+
+    match block_index {
+        Some(block_index) => {
+            settle_transfer(key, transfer_id, Some(block_index));
+            BountyResult::Success(format!("Transfer {} recorded at block {}", transfer_id, block_index))
+        }
+        None => {
+            with_bounty_mut(key, |bounty| bounty.pending_transfers.retain(|t| t.id != transfer_id));
+            BountyResult::Success(format!("Transfer {} discarded", transfer_id))
+        }
+    }
+}
+
+// Pull `amount` from `from` into a subaccount of this canister using the
+// allowance `from` granted this canister via icrc2_approve
+pub(crate) async fn ledger_transfer_from(
+    ledger: Principal,
+    memo: icrc1::transfer::Memo,
+    from: Principal,
+    to_subaccount: Subaccount,
+    amount: u64,
+) -> Result<u64, BountyError> {
+    let transfer_from_args = icrc2::transfer_from::TransferFromArgs {
+        spender_subaccount: None,
+        from: Account { owner: from, subaccount: None },
+        to: escrow_icrc_account(to_subaccount),
+        amount: amount.into(),
+        fee: Some(crate::tokens::token_fee(ledger).into()),
+        memo: Some(memo),
+        created_at_time: Some(time()),
+    };
+
+    match icrc2_transfer_from(ledger, transfer_from_args).await {
+        Ok((Ok(block_index),)) => nat_to_u64(block_index),
+        Ok((Err(transfer_error),)) => Err(BountyError::TransferFailed(format!("{:?}", transfer_error))),
+        Err((code, msg)) => Err(BountyError::TransferFailed(format!("Call failed: {}: {}", code as u8, msg))),
+    }
+}
+
+// Deposit made with icrc2_transfer_from from the default account of `from`,
+// recorded so the ledger scanner does not count it again
+pub(crate) fn icrc2_deposit(from: Principal, amount: u64, block_index: u64) -> BountyDeposit {
+    BountyDeposit {
+        block_index,
+        from: Some(AccountIdentifier::new(&from, &DEFAULT_SUBACCOUNT).to_hex()),
+        amount,
+        timestamp: time(),
+    }
+}
+
+// Book a completed critic payout and move the bounty to PartiallyPaid or Paid
+fn record_critic_payout(key: BountyKey, critic: Principal, amount: u64, fee: u64, block_index: Option<u64>) {
+    with_bounty_mut(key, |bounty| {
+        bounty.paid_amount += amount;
+        bounty.fees_paid += fee;
+        bounty.recipient = Some(critic);
+
+        let next = if bounty.paid_amount >= bounty.intended_amount || bounty.remaining() <= bounty.fee() {
+            BountyStatus::Paid
+        } else {
+            BountyStatus::PartiallyPaid
+        };
+        if let Err(e) = bounty.transition(next, Some(amount), block_index, "paid critic") {
+            ic_cdk::println!("Bounty {:?} payout not logged: {:?}", key, e);
+        }
+        // Payouts after the deadline (auto-distribution) leave the remainder expired
+        bounty.expire_if_due();
+    });
+}
+
+// Mark a critique as rewarded with the given payout
+fn record_critique_reward(artwork_id: u64, critique_id: u64, reward: CritiqueReward) {
+    crate::ARTWORKS.with(|artworks| {
+        let mut artworks = artworks.borrow_mut();
+        if let Some(critique) = artworks
+            .iter_mut()
+            .find(|a| a.id == artwork_id)
+            .and_then(|a| a.critiques.iter_mut().find(|c| c.id == critique_id))
+        {
+            critique.is_rewarded = Some(true);
+            critique.reward = Some(reward);
+        }
+    });
+}
+
+/// Split a bounty across several critiques of the artwork (only artwork author can do this).
+/// Shares are fixed amounts or percentages of the escrow left after one ledger fee per payout.
+#[update]
+pub async fn award_bounty(artwork_id: u64, awards: Vec<CritiqueAward>) -> Result<Vec<AwardPayout>, BountyError> {
+    let caller_principal = caller();
+    let _lock = BountyLock::acquire(artwork_id)?;
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let artwork = crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .cloned()
+    }).ok_or(BountyError::NotFound)?;
+
+    if artwork.author != caller_principal {
+        return Err(BountyError::NotAuthorized);
+    }
+
+    let bounty = artwork.bounty.as_ref().ok_or(BountyError::NotFound)?;
+    match bounty.status {
+        BountyStatus::Prepared => return Err(BountyError::NotFunded),
+        BountyStatus::Expired => return Err(BountyError::Expired),
+        status if !status.is_payable() => return Err(BountyError::AlreadyReleased),
+        _ => {}
+    }
+
+    if awards.is_empty() {
+        return Err(BountyError::InvalidAmount);
+    }
+
+    // Resolve every award to a critic before moving any funds
+    let mut recipients: Vec<(u64, Principal)> = Vec::new();
+    for award in &awards {
+        if recipients.iter().any(|(id, _)| *id == award.critique_id) {
+            return Err(BountyError::InvalidAmount);
+        }
+        let critique = artwork.critiques
+            .iter()
+            .find(|c| c.id == award.critique_id)
+            .ok_or(BountyError::NotFound)?;
+        if critique.reward.is_some() || bounty.has_pending_payout(critique.id) {
+            return Err(BountyError::AlreadyReleased);
+        }
+        if bounty.is_disputed(critique.id) {
+            return Err(BountyError::InvalidTransition(format!("critique {} is under dispute", critique.id)));
+        }
+        if critique.critic == artwork.author {
+            return Err(BountyError::NotAuthorized);
+        }
+        recipients.push((critique.id, critique.critic));
+    }
+
+    let shares: Vec<AwardShare> = awards.into_iter().map(|a| a.share).collect();
+    let amounts = award_amounts(bounty, reserved_for_critics(artwork_id, bounty, None), &shares)?;
+
+    let payouts = recipients
+        .into_iter()
+        .zip(amounts)
+        .map(|((critique_id, critic), amount)| (critique_id, critic, amount))
+        .collect();
+    Ok(pay_critiques(artwork_id, payouts).await)
+}
+
+// `percent` of `amount`, in u128 so amounts of tokens with many decimals cannot overflow
+fn percent_of(amount: u64, percent: u8) -> u64 {
+    (amount as u128 * percent.min(100) as u128 / 100) as u64
+}
+
+/// Escrow funds held back for claims and disputes on an artwork's bounty that
+/// may still be paid, with one ledger fee each. Those over `except_critique`
+/// are not counted.
+pub(crate) fn reserved_for_critics(artwork_id: u64, bounty: &Bounty, except_critique: Option<u64>) -> u64 {
+    let fee = bounty.fee();
+    let disputes = bounty.disputes
+        .iter()
+        .filter(|d| d.is_unresolved())
+        .map(|d| (d.critique_id, d.amount));
+    crate::bounty_claims::unresolved_claims(artwork_id)
+        .into_iter()
+        .chain(disputes)
+        .filter(|(critique_id, _)| Some(*critique_id) != except_critique)
+        .map(|(_, amount)| amount.saturating_add(fee))
+        .fold(0, u64::saturating_add)
+}
+
+// Resolve award shares to amounts, checking they fit in the escrow after one
+// ledger fee per payout and the `reserved` funds
+pub(crate) fn award_amounts(bounty: &Bounty, reserved: u64, shares: &[AwardShare]) -> Result<Vec<u64>, BountyError> {
+    let fees = bounty.fee().checked_mul(shares.len() as u64).ok_or(BountyError::InsufficientFunds)?;
+    let distributable = bounty.remaining()
+        .checked_sub(reserved)
+        .and_then(|left| left.checked_sub(fees))
+        .ok_or(BountyError::InsufficientFunds)?;
+
+    let total_percent: u64 = shares.iter().map(|share| match share {
+        AwardShare::Percent(p) => *p as u64,
+        AwardShare::Amount(_) => 0,
+    }).sum();
+    if total_percent > 100 {
+        return Err(BountyError::InvalidAmount);
+    }
+
+    let amounts: Vec<u64> = shares.iter().map(|share| match share {
+        AwardShare::Amount(amount) => *amount,
+        AwardShare::Percent(p) => percent_of(distributable, *p),
+    }).collect();
+    if amounts.contains(&0) {
+        return Err(BountyError::InvalidAmount);
+    }
+    if amounts.iter().map(|a| *a as u128).sum::<u128>() > distributable as u128 {
+        return Err(BountyError::InsufficientFunds);
+    }
+    Ok(amounts)
+}
+
+// Pay each (critique, critic, amount) from the escrow and record the payout
+// on both the bounty and the critique. A failed transfer does not stop the rest.
+// Callers must hold the bounty lock.
+async fn pay_critiques(artwork_id: u64, payouts: Vec<(u64, Principal, u64)>) -> Vec<AwardPayout> {
+    let mut results = Vec::new();
+    for (critique_id, critic, amount) in payouts {
+        let kind = TransferKind::CriticPayout { critique_id: Some(critique_id) };
+        match send_from_escrow(artwork_id, kind, critic, amount, "awarded critique").await {
+            Ok(block_index) => {
+                results.push(AwardPayout { critique_id, amount, block_index: Some(block_index), error: None });
+            }
+            Err(e) => {
+                results.push(AwardPayout { critique_id, amount, block_index: None, error: Some(format!("{:?}", e)) });
+            }
+        }
+    }
+    results
+}
+
+/// Opt in to automatic distribution at expiry (only artwork author can do this).
+/// `split` gives the percent for the 1st, 2nd, ... most upvoted critique. Once the
+/// bounty is funded the policy can no longer be changed or removed.
+#[update]
+pub fn set_bounty_auto_distribution(artwork_id: u64, split: Vec<u8>) -> BountyResult {
+    if split.len() > 10 || split.contains(&0) || split.iter().map(|p| *p as u64).sum::<u64>() > 100 {
+        return BountyResult::Error(BountyError::InvalidAmount);
     }
 
-    // Check if bounty has expired
-    if let Some(expires_at) = bounty.expires_at {
-        if time() > expires_at {
+    let result = crate::ARTWORKS.with(|artworks| {
+        let mut artworks = artworks.borrow_mut();
+        let artwork = artworks
+            .iter_mut()
+            .find(|a| a.id == artwork_id)
+            .ok_or(BountyError::NotFound)?;
+        if artwork.author != caller() {
+            return Err(BountyError::NotAuthorized);
+        }
+
+        let bounty = artwork.bounty.as_mut().ok_or(BountyError::NotFound)?;
+        bounty.expire_if_due();
+        let expires_at = bounty.expires_at.ok_or(BountyError::NotReady)?;
+        let locked = bounty.auto_distribution.is_some() && bounty.status != BountyStatus::Prepared;
+        if locked || !matches!(bounty.status, BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid) {
+            return Err(BountyError::InvalidTransition(format!("auto-distribution locked in {:?}", bounty.status)));
+        }
+
+        bounty.auto_distribution = if split.is_empty() {
+            None
+        } else {
+            Some(AutoDistribution { split: split.clone(), executed_at: None })
+        };
+        Ok(expires_at)
+    });
+
+    match result {
+        Ok(_) if split.is_empty() => BountyResult::Success("Auto-distribution removed".to_string()),
+        Ok(expires_at) => {
+            schedule_auto_distribution(artwork_id, expires_at);
+            BountyResult::Success(format!(
+                "Bounty will be split {:?} across the top critiques when it expires",
+                split
+            ))
+        }
+        Err(e) => BountyResult::Error(e),
+    }
+}
+
+// Arm a one-shot timer that runs the distribution just after the deadline
+pub(crate) fn schedule_auto_distribution(artwork_id: u64, expires_at: u64) {
+    let delay = Duration::from_nanos(expires_at.saturating_sub(time()) + 1);
+    ic_cdk_timers::set_timer(delay, move || {
+        ic_cdk::spawn(async move {
+            if let Err(e) = run_auto_distribution(artwork_id).await {
+                ic_cdk::println!("Auto-distribution for bounty {} failed: {:?}", artwork_id, e);
+            }
+        });
+    });
+}
+
+/// Pay out an expired bounty to its most upvoted critiques according to its
+/// auto-distribution policy. Runs at most once per bounty.
+pub(crate) async fn run_auto_distribution(artwork_id: u64) -> Result<Vec<AwardPayout>, BountyError> {
+    let _lock = BountyLock::acquire(artwork_id)?;
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let artwork = crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .cloned()
+    }).ok_or(BountyError::NotFound)?;
+    let bounty = artwork.bounty.as_ref().ok_or(BountyError::NotFound)?;
+    let policy = bounty.auto_distribution.as_ref().ok_or(BountyError::NotFound)?;
+
+    if policy.executed_at.is_some() {
+        return Err(BountyError::AlreadyReleased);
+    }
+    if bounty.status != BountyStatus::Expired {
+        return Err(BountyError::NotReady);
+    }
+
+    // Mark as executed before any await so the distribution cannot run twice
+    with_bounty_mut(artwork_id, |bounty| {
+        if let Some(policy) = bounty.auto_distribution.as_mut() {
+            policy.executed_at = Some(time());
+        }
+    });
+    if bounty.funded_at.is_none() {
+        return Ok(vec![]);
+    }
+
+    // Rank each critic's best unrewarded critique by upvotes, earliest first on ties
+    let mut ranked: Vec<&crate::Critique> = Vec::new();
+    for critique in artwork.critiques.iter().filter(|c| {
+        c.upvotes > 0 && c.reward.is_none() && c.critic != artwork.author && !bounty.is_disputed(c.id)
+    }) {
+        match ranked.iter_mut().find(|best| best.critic == critique.critic) {
+            Some(best) if critique.upvotes > best.upvotes => *best = critique,
+            Some(_) => {}
+            None => ranked.push(critique),
+        }
+    }
+    ranked.sort_by(|a, b| b.upvotes.cmp(&a.upvotes).then(a.id.cmp(&b.id)));
+    ranked.truncate(policy.split.len());
+
+    let fees = bounty.fee().saturating_mul(ranked.len() as u64);
+    let reserved = reserved_for_critics(artwork_id, bounty, None);
+    let distributable = bounty.remaining().saturating_sub(reserved).saturating_sub(fees);
+    let payouts: Vec<(u64, Principal, u64)> = ranked
+        .iter()
+        .zip(&policy.split)
+        .map(|(c, percent)| (c.id, c.critic, percent_of(distributable, *percent)))
+        .filter(|(_, _, amount)| *amount > 0)
+        .collect();
+
+    Ok(pay_critiques(artwork_id, payouts).await)
+}
+
+/// Confirm on the ledger that the escrow account holds the bounty. Records the
+/// received amount and marks the bounty funded once it covers the intended amount.
+#[update]
+pub async fn confirm_bounty_funding(artwork_id: u64) -> BountyResult {
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let artwork = crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .cloned()
+    });
+
+    let artwork = match artwork {
+        Some(art) => art,
+        None => return BountyResult::Error(BountyError::NotFound),
+    };
+
+    let bounty = match &artwork.bounty {
+        Some(b) => b,
+        None => return BountyResult::Error(BountyError::NotFound),
+    };
+
+    match bounty.status {
+        BountyStatus::Prepared => {}
+        BountyStatus::Expired if bounty.funded_at.is_none() => {
             return BountyResult::Error(BountyError::Expired);
         }
+        status => {
+            return BountyResult::Success(format!(
+                "Bounty for artwork {} already funded with {} (status: {:?})",
+                artwork_id, crate::tokens::format_amount(bounty.ledger, bounty.actual_amount), status
+            ));
+        }
+    }
+
+    let balance = match escrow_balance(artwork_id).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    let funded = with_bounty_mut(artwork_id, |bounty| {
+        bounty.actual_amount = balance;
+        if balance >= bounty.intended_amount && bounty.status == BountyStatus::Prepared {
+            bounty.funded_at = Some(time());
+            bounty.transition(BountyStatus::Funded, Some(balance), None, "escrow balance confirmed").is_ok()
+        } else {
+            false
+        }
+    });
+
+    match funded {
+        Some(true) => BountyResult::Success(format!(
+            "Bounty for artwork {} funded with {}",
+            artwork_id, crate::tokens::format_amount(bounty.ledger, balance)
+        )),
+        Some(false) => BountyResult::Error(BountyError::InsufficientFunds),
+        None => BountyResult::Error(BountyError::NotFound),
+    }
+}
+
+/// Fund a prepared bounty in one step: the author first calls `icrc2_approve` on
+/// the bounty's ledger with this canister as spender, then this pulls `amount` from
+/// the author's account into the bounty escrow via `icrc2_transfer_from`.
+#[update]
+pub async fn fund_bounty_with_approval(artwork_id: u64, amount: u64) -> BountyResult {
+    let caller_principal = caller();
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let (author, subaccount, ledger) = match crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .map(|a| (a.author, a.bounty.clone()))
+    }) {
+        Some((author, Some(bounty))) => {
+            if author != caller_principal {
+                return BountyResult::Error(BountyError::NotAuthorized);
+            }
+            match bounty.status {
+                BountyStatus::Prepared => {}
+                BountyStatus::Expired => return BountyResult::Error(BountyError::Expired),
+                status => {
+                    return BountyResult::Error(BountyError::InvalidTransition(format!(
+                        "Bounty is already {:?}", status
+                    )));
+                }
+            }
+            let subaccount = stored_subaccount(artwork_id, author, &bounty);
+            (author, subaccount, bounty.ledger)
+        }
+        _ => return BountyResult::Error(BountyError::NotFound),
+    };
+
+    if amount == 0 {
+        return BountyResult::Error(BountyError::InvalidAmount);
     }
 
-    /* testing */
-    if LOCAL_TESTING {
-        // Skip balance check and mock the transfer
-        crate::ARTWORKS.with(|artworks| {
-            let mut artworks = artworks.borrow_mut();
-            if let Some(artwork) = artworks.iter_mut().find(|a| a.id == artwork_id) {
-                if let Some(ref mut bounty) = artwork.bounty {
-                    bounty.released = true;
-                    bounty.recipient = Some(critic_principal);
+    let block_index = match ledger_transfer_from(ledger, artwork_id.into(), author, subaccount, amount).await {
+        Ok(block_index) => block_index,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    let funded = with_bounty_mut(artwork_id, |bounty| {
+        bounty.subaccount = Some(subaccount);
+        if !bounty.has_deposit(block_index) {
+            bounty.actual_amount += amount;
+            bounty.deposits.push(icrc2_deposit(author, amount, block_index));
+        }
+        if bounty.actual_amount >= bounty.intended_amount && bounty.status == BountyStatus::Prepared {
+            bounty.funded_at = Some(time());
+            let _ = bounty.transition(
+                BountyStatus::Funded,
+                Some(bounty.actual_amount),
+                Some(block_index),
+                "funded via icrc2_transfer_from",
+            );
+        }
+        bounty.status == BountyStatus::Funded
+    });
+
+    match funded {
+        Some(true) => BountyResult::Success(format!(
+            "Bounty for artwork {} funded with {} (block {})",
+            artwork_id, crate::tokens::format_amount(ledger, amount), block_index
+        )),
+        Some(false) => BountyResult::Success(format!(
+            "Received {} for artwork {} (block {}); the bounty is not fully funded yet",
+            crate::tokens::format_amount(ledger, amount), artwork_id, block_index
+        )),
+        None => BountyResult::Error(BountyError::NotFound),
+    }
+}
+
+/// Add funds to an artwork's bounty. Anyone can sponsor a bounty that is still
+/// open: the caller first calls `icrc2_approve` on the bounty's ledger with this
+/// canister as spender. Funds added after funding raise the bounty amount;
+/// sponsors get their pro rata share back if the bounty is withdrawn.
+#[update]
+pub async fn contribute_to_bounty(artwork_id: u64, amount: u64) -> BountyResult {
+    let contributor = caller();
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let (author, title, subaccount, ledger) = match crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .map(|a| (a.author, a.title.clone(), a.bounty.clone()))
+    }) {
+        Some((author, title, Some(bounty))) => {
+            match bounty.status {
+                BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid => {}
+                BountyStatus::Expired => return BountyResult::Error(BountyError::Expired),
+                status => {
+                    return BountyResult::Error(BountyError::InvalidTransition(format!(
+                        "Bounty is already {:?}", status
+                    )));
                 }
             }
-        });
+            let subaccount = stored_subaccount(artwork_id, author, &bounty);
+            (author, title, subaccount, bounty.ledger)
+        }
+        _ => return BountyResult::Error(BountyError::NotFound),
+    };
 
-        return BountyResult::Success(format!(
-            "TEST MODE: Successfully transferred {} ICP to critic {}",
-            amount as f64 / 100_000_000.0,
-            critic_principal.to_text()
-        ));
+    if amount == 0 {
+        return BountyResult::Error(BountyError::InvalidAmount);
     }
-    /* testing */
 
-    // Perform the transfer
-    let subaccount = bounty.subaccount.unwrap_or(DEFAULT_SUBACCOUNT);
-    let transfer_args = TransferArgs {
-        memo: Memo(artwork_id),
-        amount: Tokens::from_e8s(amount),
-        fee: Tokens::from_e8s(ICP_FEE),
-        from_subaccount: Some(subaccount),
-        to: AccountIdentifier::new(&critic_principal, &DEFAULT_SUBACCOUNT),
-        created_at_time: None,
+    let block_index = match ledger_transfer_from(ledger, artwork_id.into(), contributor, subaccount, amount).await {
+        Ok(block_index) => block_index,
+        Err(e) => return BountyResult::Error(e),
     };
 
-    match ic_cdk::call::<(TransferArgs,), (Result<BlockIndex, TransferError>,)>(
-        ledger_canister_id(),
-        "transfer",
-        (transfer_args,),
-    )
-    .await
-    {
-        Ok((Ok(block_index),)) => {
-            // Update bounty status if this was the full amount
-            if amount >= bounty.intended_amount {
-                crate::ARTWORKS.with(|artworks| {
-                    let mut artworks = artworks.borrow_mut();
-                    if let Some(artwork) = artworks.iter_mut().find(|a| a.id == artwork_id) {
-                        if let Some(ref mut bounty) = artwork.bounty {
-                            bounty.released = true;
-                            bounty.recipient = Some(critic_principal);
-                        }
-                    }
-                });
+    let total = with_bounty_mut(artwork_id, |bounty| {
+        bounty.subaccount = Some(subaccount);
+        // Counted already if the deposit scanner got to the block first
+        if !bounty.has_deposit(block_index) {
+            bounty.actual_amount += amount;
+            bounty.deposits.push(icrc2_deposit(contributor, amount, block_index));
+            if bounty.status != BountyStatus::Prepared {
+                bounty.intended_amount += amount;
             }
-
-            BountyResult::Success(format!(
-                "Successfully transferred {} ICP to critic {}. Block index: {}",
-                amount as f64 / 100_000_000.0,
-                critic_principal.to_text(),
-                block_index
-            ))
         }
-        Ok((Err(transfer_error),)) => {
-            BountyResult::Error(BountyError::TransferFailed(format!("{:?}", transfer_error)))
+        if bounty.status == BountyStatus::Prepared && bounty.actual_amount >= bounty.intended_amount {
+            bounty.funded_at = Some(time());
+            let _ = bounty.transition(
+                BountyStatus::Funded,
+                Some(bounty.actual_amount),
+                Some(block_index),
+                "funded by sponsor contribution",
+            );
         }
-        Err((code, msg)) => {
-            BountyResult::Error(BountyError::TransferFailed(format!("Call failed: {}: {}", code as u8, msg)))
+
+        // The author's own top-ups are part of the author's share
+        if contributor != author {
+            match bounty.contributions.iter_mut().find(|c| c.contributor == contributor) {
+                Some(c) => {
+                    c.amount += amount;
+                    c.contributed_at = time();
+                    c.last_block_index = Some(block_index);
+                }
+                None => bounty.contributions.push(BountyContribution {
+                    contributor,
+                    amount,
+                    contributed_at: time(),
+                    last_block_index: Some(block_index),
+                    refunded: 0,
+                    refunded_at: None,
+                    refund_block: None,
+                }),
+            }
         }
+        bounty.intended_amount.max(bounty.actual_amount)
+    });
+    let Some(total) = total else {
+        return BountyResult::Error(BountyError::NotFound);
+    };
+
+    if contributor != author {
+        crate::notifications::notify(author, Some(artwork_id), format!(
+            "A sponsor added {} to the bounty on \"{}\". It now stands at {}.",
+            crate::tokens::format_amount(ledger, amount),
+            title,
+            crate::tokens::format_amount(ledger, total)
+        ));
     }
+    BountyResult::Success(format!(
+        "Added {} to the bounty for artwork {}. {}",
+        crate::tokens::format_amount(ledger, amount),
+        artwork_id,
+        block_note(block_index)
+    ))
 }
 
-/// ✅ COMPLETELY FIXED - Get the balance of a bounty escrow account
+/// ICRC-1 escrow account of an artwork's bounty (canister principal + subaccount)
 #[query]
-pub async fn get_bounty_balance(artwork_id: u64) -> BountyResult {
-    let artwork = crate::ARTWORKS.with(|artworks| {
+pub fn get_bounty_escrow_icrc_account(artwork_id: u64) -> Option<Account> {
+    crate::ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
             .find(|a| a.id == artwork_id)
-            .cloned()
-    });
+            .and_then(|a| a.bounty.as_ref().map(|b| bounty_escrow_account(artwork_id, a.author, b)))
+    })
+}
 
-    let artwork = match artwork {
-        Some(art) => art,
-        None => return BountyResult::Error(BountyError::NotFound),
-    };
+/// Balance of a bounty escrow account
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct BountyBalance {
+    pub e8s: u64, // in the token's smallest unit
+    pub token: crate::tokens::TokenInfo,
+    pub account: Account,
+}
 
-    // ✅ CRITICAL FIX - Use correct AccountBalanceArgs struct
-    let account_id = get_bounty_account_identifier(artwork_id, artwork.author);
-    
-    let balance_args = AccountBalanceArgs {
-        account: account_id, // ✅ Direct AccountIdentifier, not converted
-    };
+// Current escrow balance of an artwork's bounty, read from the bounty's ledger.
+// Artworks without a bounty report their ICP escrow.
+pub(crate) async fn escrow_balance(key: impl Into<BountyKey>) -> Result<BountyBalance, BountyError> {
+    let (ledger, account) = match key.into() {
+        BountyKey::Artwork(artwork_id) => crate::ARTWORKS.with(|artworks| {
+            artworks.borrow()
+                .iter()
+                .find(|a| a.id == artwork_id)
+                .map(|a| match &a.bounty {
+                    Some(bounty) => (bounty.ledger, bounty_escrow_account(a.id, a.author, bounty)),
+                    None => (ledger_canister_id(), escrow_icrc_account(generate_bounty_subaccount(a.id, a.author))),
+                })
+        }),
+        BountyKey::Request(request_id) => crate::critique_requests::request_escrow(request_id)
+            .map(|(ledger, _, subaccount)| (ledger, escrow_icrc_account(subaccount))),
+    }.ok_or(BountyError::NotFound)?;
 
-    match ic_cdk::call::<(AccountBalanceArgs,), (Tokens,)>(
-        ledger_canister_id(),
-        "account_balance", // ✅ Use standard method name
-        (balance_args,),
-    )
-    .await
-    {
-        Ok((balance,)) => {
-            BountyResult::Success(format!(
-                "Balance: {} ICP ({} e8s)",
-                balance.e8s() as f64 / 100_000_000.0,
-                balance.e8s()
-            ))
-        }
-        Err((code, msg)) => {
-            BountyResult::Error(BountyError::TransferFailed(format!("Balance check failed: {}: {}", code as u8, msg)))
-        }
-    }
+    let e8s = ledger_balance(ledger, account).await?;
+
+    Ok(BountyBalance {
+        e8s,
+        token: crate::tokens::token_or_placeholder(ledger),
+        account,
+    })
+}
+
+/// Get the balance of a bounty escrow account. An update call, since it
+/// queries the ledger canister.
+#[update]
+pub async fn get_bounty_balance(artwork_id: u64) -> Result<BountyBalance, BountyError> {
+    escrow_balance(artwork_id).await
 }
 
 /// Get bounty escrow account identifier as hex string (for frontend wallet integration)
@@ -287,11 +1710,13 @@ pub fn get_bounty_escrow_account_id(artwork_id: u64, author: Principal) -> Strin
     account_id.to_string()
 }
 
-/// Withdraw remaining bounty funds (only author can do this after expiration or if no critiques)
+/// Cancel a bounty that was never funded (author only). Fails if the escrow holds any funds.
 #[update]
-pub async fn withdraw_bounty(artwork_id: u64) -> BountyResult {
-    let caller_principal = caller();
-    
+pub async fn cancel_bounty(artwork_id: u64) -> BountyResult {
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
     let artwork = crate::ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
@@ -304,7 +1729,7 @@ pub async fn withdraw_bounty(artwork_id: u64) -> BountyResult {
         None => return BountyResult::Error(BountyError::NotFound),
     };
 
-    if artwork.author != caller_principal {
+    if artwork.author != caller() {
         return BountyResult::Error(BountyError::NotAuthorized);
     }
 
@@ -313,76 +1738,88 @@ pub async fn withdraw_bounty(artwork_id: u64) -> BountyResult {
         None => return BountyResult::Error(BountyError::NotFound),
     };
 
-    // Check if bounty can be withdrawn (expired or no critiques after reasonable time)
-    let can_withdraw = if let Some(expires_at) = bounty.expires_at {
-        time() > expires_at
-    } else {
-        // If no expiration set, allow withdrawal after 7 days with no critiques
-        time() > bounty.created_at + 7 * 24 * 60 * 60 * 1_000_000_000 && artwork.critiques.is_empty()
-    };
-
-    if !can_withdraw && !bounty.released {
-        return BountyResult::Error(BountyError::NotReady);
+    if bounty.status != BountyStatus::Prepared {
+        return BountyResult::Error(BountyError::InvalidTransition(format!("{:?} -> Cancelled", bounty.status)));
     }
 
-    // Get current balance
-    let available_balance = match get_bounty_balance(artwork_id).await {
-        BountyResult::Success(balance_str) => {
-            // Extract e8s from the formatted string "Balance: X ICP (Y e8s)"
-            if let Some(start) = balance_str.find("(") {
-                if let Some(end) = balance_str.find(" e8s)") {
-                    balance_str[start+1..end].parse::<u64>().unwrap_or(0)
-                } else { 0 }
-            } else { 0 }
-        },
-        BountyResult::Error(e) => return BountyResult::Error(e),
+    let balance = match escrow_balance(artwork_id).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => return BountyResult::Error(e),
     };
-
-    if available_balance <= ICP_FEE {
-        return BountyResult::Error(BountyError::InsufficientFunds);
+    if balance > 0 {
+        // Deposits already arrived: confirm funding or wait for expiry and withdraw instead
+        return BountyResult::Error(BountyError::NotReady);
     }
 
-    let withdraw_amount = available_balance - ICP_FEE;
-    let subaccount = bounty.subaccount.unwrap_or(DEFAULT_SUBACCOUNT);
+    match with_bounty_mut(artwork_id, |bounty| bounty.transition(BountyStatus::Cancelled, None, None, "cancelled by author")) {
+        Some(Ok(())) => BountyResult::Success(format!("Bounty for artwork {} cancelled", artwork_id)),
+        Some(Err(e)) => BountyResult::Error(e),
+        None => BountyResult::Error(BountyError::NotFound),
+    }
+}
 
-    let transfer_args = TransferArgs {
-        memo: Memo(artwork_id),
-        amount: Tokens::from_e8s(withdraw_amount),
-        fee: Tokens::from_e8s(ICP_FEE),
-        from_subaccount: Some(subaccount),
-        to: AccountIdentifier::new(&caller_principal, &DEFAULT_SUBACCOUNT),
-        created_at_time: None,
+/// Choose the token of a bounty from the supported tokens (author only). Only
+/// possible while the bounty is prepared and nothing has been deposited yet.
+#[update]
+pub fn set_bounty_token(artwork_id: u64, ledger: Principal) -> BountyResult {
+    let token = match crate::tokens::token_info(ledger) {
+        Some(token) if token.enabled => token,
+        _ => return BountyResult::Error(BountyError::UnsupportedToken),
     };
 
-    match ic_cdk::call::<(TransferArgs,), (Result<BlockIndex, TransferError>,)>(
-        ledger_canister_id(),
-        "transfer", 
-        (transfer_args,),
-    )
-    .await
-    {
-        Ok((Ok(block_index),)) => {
-            BountyResult::Success(format!(
-                "Successfully withdrew {} ICP. Block index: {}",
-                withdraw_amount as f64 / 100_000_000.0,
-                block_index
-            ))
-        }
-        Ok((Err(transfer_error),)) => {
-            BountyResult::Error(BountyError::TransferFailed(format!("{:?}", transfer_error)))
+    let result = crate::ARTWORKS.with(|artworks| {
+        let mut artworks = artworks.borrow_mut();
+        let artwork = artworks
+            .iter_mut()
+            .find(|a| a.id == artwork_id)
+            .ok_or(BountyError::NotFound)?;
+        if artwork.author != caller() {
+            return Err(BountyError::NotAuthorized);
         }
-        Err((code, msg)) => {
-            BountyResult::Error(BountyError::TransferFailed(format!("Call failed: {}: {}", code as u8, msg)))
+
+        let bounty = artwork.bounty.as_mut().ok_or(BountyError::NotFound)?;
+        if bounty.status != BountyStatus::Prepared || bounty.actual_amount > 0 {
+            return Err(BountyError::InvalidTransition(format!(
+                "token can only change before funding ({:?})", bounty.status
+            )));
         }
+        bounty.ledger = ledger;
+        let subaccount = stored_subaccount(artwork_id, artwork.author, bounty);
+        crate::escrow_audit::remember_escrow(BountyKey::Artwork(artwork_id), ledger, subaccount);
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => BountyResult::Success(format!(
+            "Bounty for artwork {} is now paid in {}",
+            artwork_id, token.symbol
+        )),
+        Err(e) => BountyResult::Error(e),
     }
 }
 
-/// Claim a bounty (for critics - alternative to author transfer)
+/// Withdraw remaining bounty funds (only author can do this after expiration or if no critiques)
 #[update]
-pub async fn claim_bounty(artwork_id: u64) -> BountyResult {
+pub async fn withdraw_bounty(artwork_id: u64) -> BountyResult {
     let caller_principal = caller();
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    // A pending vote-based distribution always runs before the author can reclaim funds
+    let distribution_pending = with_bounty_mut(artwork_id, |bounty| {
+        bounty.status == BountyStatus::Expired
+            && bounty.auto_distribution.as_ref().is_some_and(|d| d.executed_at.is_none())
+    });
+    if distribution_pending == Some(true) {
+        if let Err(e) = run_auto_distribution(artwork_id).await {
+            ic_cdk::println!("Auto-distribution for bounty {} failed: {:?}", artwork_id, e);
+        }
+    }
+
+    let _lock = match BountyLock::acquire(artwork_id) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
     
-    // Verify the caller has posted a critique for this artwork
     let artwork = crate::ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
@@ -395,9 +1832,7 @@ pub async fn claim_bounty(artwork_id: u64) -> BountyResult {
         None => return BountyResult::Error(BountyError::NotFound),
     };
 
-    // Check if caller has critiques on this artwork
-    let has_critique = artwork.critiques.iter().any(|c| c.critic == caller_principal);
-    if !has_critique {
+    if artwork.author != caller_principal {
         return BountyResult::Error(BountyError::NotAuthorized);
     }
 
@@ -406,30 +1841,195 @@ pub async fn claim_bounty(artwork_id: u64) -> BountyResult {
         None => return BountyResult::Error(BountyError::NotFound),
     };
 
-    if bounty.released {
+    if bounty.status.is_settled() {
         return BountyResult::Error(BountyError::AlreadyReleased);
     }
+    if bounty.has_open_dispute() || !crate::bounty_claims::unresolved_claims(artwork_id).is_empty() {
+        return BountyResult::Error(BountyError::NotReady);
+    }
+    if !bounty.pending_transfers.is_empty() {
+        return BountyResult::Error(BountyError::TransferPending(
+            "earlier transfers are unresolved; call reconcile_bounty_transfers first".to_string(),
+        ));
+    }
+
+    // Check if bounty can be withdrawn (expired, fully paid, or no critiques after reasonable time)
+    let can_withdraw = match bounty.status {
+        BountyStatus::Expired | BountyStatus::Paid => true,
+        // If no expiration set, allow withdrawal after 7 days with no critiques
+        BountyStatus::Funded if bounty.expires_at.is_none() => {
+            time() > bounty.created_at + config().unclaimed_withdraw_after_ns && artwork.critiques.is_empty()
+        }
+        _ => false,
+    };
+
+    if !can_withdraw {
+        return BountyResult::Error(BountyError::NotReady);
+    }
+
+    // Get current balance
+    let available_balance = match escrow_balance(artwork_id).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    if available_balance <= bounty.fee() {
+        return BountyResult::Error(BountyError::InsufficientFunds);
+    }
+
+    match refund_escrow(artwork_id, caller_principal, available_balance, "refunded to author").await {
+        Ok(outcome) => BountyResult::Success(outcome.message(bounty.ledger)),
+        Err(e) => BountyResult::Error(e),
+    }
+}
+
+/// What the author received from `refund_escrow`
+#[derive(Debug)]
+pub(crate) enum RefundOutcome {
+    Sent { amount: u64, block_index: u64 },
+    NothingLeft, // the sponsors' shares took everything above the ledger fee
+}
+
+impl RefundOutcome {
+    /// Result message for the author
+    pub(crate) fn message(&self, ledger: Principal) -> String {
+        match self {
+            RefundOutcome::Sent { amount, block_index } => format!(
+                "Successfully withdrew {}. {}",
+                crate::tokens::format_amount(ledger, *amount),
+                block_note(*block_index)
+            ),
+            RefundOutcome::NothingLeft => {
+                "The remaining funds went back to the bounty's sponsors; nothing was left to withdraw".to_string()
+            }
+        }
+    }
+}
+
+// Split `pool` pro rata between the sponsors not refunded yet and the author,
+// who owns whatever the sponsors did not put in. Returns the sponsors' shares.
+fn sponsor_refund_shares(bounty: &Bounty, pool: u64) -> Vec<(Principal, u64)> {
+    let refunded: u64 = bounty.contributions
+        .iter()
+        .filter(|c| c.refunded_at.is_some())
+        .map(|c| c.amount)
+        .sum();
+    let base = bounty.actual_amount.saturating_sub(refunded).max(bounty.sponsored_amount() - refunded);
+    if base == 0 {
+        return vec![];
+    }
+    bounty.contributions
+        .iter()
+        .filter(|c| c.refunded_at.is_none())
+        .map(|c| (c.contributor, (pool as u128 * c.amount as u128 / base as u128) as u64))
+        .collect()
+}
+
+// Return an escrow `balance` to the people who funded it: each sponsor gets a
+// pro rata share, the author the rest, and the bounty is marked refunded.
+// Shares too small to cover the ledger fee stay with the author's part.
+// Returns what reached the author, if anything. Callers must hold the bounty lock.
+pub(crate) async fn refund_escrow(
+    key: impl Into<BountyKey>,
+    author: Principal,
+    balance: u64,
+    note: &str,
+) -> Result<RefundOutcome, BountyError> {
+    let key = key.into();
+    let (shares, fee, ledger) = with_bounty_mut(key, |bounty| {
+        (sponsor_refund_shares(bounty, balance), bounty.fee(), bounty.ledger)
+    }).ok_or(BountyError::NotFound)?;
+
+    let mut left = balance;
+    for (sponsor, share) in shares {
+        if share <= fee {
+            continue;
+        }
+        let block_index = send_from_escrow(key, TransferKind::SponsorRefund, sponsor, share - fee, "pro rata refund to sponsor").await?;
+        left -= share;
+        crate::notifications::notify(sponsor, key.artwork_id(), format!(
+            "A bounty you sponsored was withdrawn: your share of {} was returned. {}.",
+            crate::tokens::format_amount(ledger, share - fee),
+            block_note(block_index)
+        ));
+    }
+
+    if left <= fee {
+        // Everything went to sponsors; nothing left to send the author
+        let closed = with_bounty_mut(key, |bounty| bounty.transition(BountyStatus::Refunded, None, None, note));
+        return match closed {
+            Some(Ok(())) => Ok(RefundOutcome::NothingLeft),
+            Some(Err(e)) => Err(e),
+            None => Err(BountyError::NotFound),
+        };
+    }
+    let amount = left - fee;
+    let block_index = send_from_escrow(key, TransferKind::Refund, author, amount, note).await?;
+    Ok(RefundOutcome::Sent { amount, block_index })
+}
+
+// Pay `amount` from the escrow to the author of a critique, e.g. for an approved
+// claim. Funds remaining after the deadline can still be paid out.
+pub(crate) async fn pay_critique(artwork_id: u64, critique_id: u64, amount: u64) -> Result<AwardPayout, BountyError> {
+    let _lock = BountyLock::acquire(artwork_id)?;
+    with_bounty_mut(artwork_id, |bounty| bounty.expire_if_due());
+
+    let artwork = crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .cloned()
+    }).ok_or(BountyError::NotFound)?;
+    let bounty = artwork.bounty.as_ref().ok_or(BountyError::NotFound)?;
+    let critique = artwork.critiques
+        .iter()
+        .find(|c| c.id == critique_id)
+        .ok_or(BountyError::NotFound)?;
+
+    let payable = bounty.status.is_payable() || (bounty.status == BountyStatus::Expired && bounty.funded_at.is_some());
+    if !payable {
+        return Err(BountyError::NotFunded);
+    }
+    if critique.reward.is_some() || bounty.has_pending_payout(critique_id) {
+        return Err(BountyError::AlreadyReleased);
+    }
+    if amount == 0 {
+        return Err(BountyError::InvalidAmount);
+    }
+    bounty.check_payable(amount, reserved_for_critics(artwork_id, bounty, Some(critique_id)))?;
 
-    // For now, require author approval (they use transfer_bounty_to_critic)
-    // This maintains quality control
-    BountyResult::Error(BountyError::NotAuthorized)
+    let kind = TransferKind::CriticPayout { critique_id: Some(critique_id) };
+    let block_index = send_from_escrow(artwork_id, kind, critique.critic, amount, "approved claim").await?;
+    Ok(AwardPayout { critique_id, amount, block_index: Some(block_index), error: None })
 }
 
 /// Get all bounties for a user (as author)
 #[query]
-pub fn get_user_bounties(user: Principal) -> Vec<(u64, Bounty)> {
+pub fn get_user_bounties(user: Principal) -> Vec<(u64, BountyView)> {
+    // Bounties on anonymous works are only listed for their own author
+    let include_anonymous = caller() == user;
     crate::ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
-            .filter(|a| a.author == user && a.bounty.is_some())
-            .map(|a| (a.id, a.bounty.clone().unwrap()))
+            .filter(|a| a.author == user && (include_anonymous || !a.anonymous))
+            .filter_map(|a| a.public_bounty().map(|bounty| (a.id, bounty)))
             .collect()
     })
 }
 
 /// Get bounty info for a specific artwork
 #[query]
-pub fn get_artwork_bounty(artwork_id: u64) -> Option<Bounty> {
+pub fn get_artwork_bounty(artwork_id: u64) -> Option<BountyView> {
+    crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id)
+            .and_then(|a| a.public_bounty())
+    })
+}
+
+// Unredacted bounty of an artwork, for internal use
+pub(crate) fn artwork_bounty(artwork_id: u64) -> Option<Bounty> {
     crate::ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
@@ -445,50 +2045,32 @@ pub fn set_artwork_bounty(artwork_id: u64, intended_amount: u64, author: Princip
     }
 
     let subaccount = generate_bounty_subaccount(artwork_id, author);
-    
+    crate::escrow_audit::remember_escrow(BountyKey::Artwork(artwork_id), ledger_canister_id(), subaccount);
+
     Some(Bounty {
         ledger: ledger_canister_id(),
         subaccount: Some(subaccount),
         intended_amount,
         actual_amount: 0,
-        released: false,
+        status: BountyStatus::Prepared,
         created_at: time(),
-        expires_at: Some(time() + 30 * 24 * 60 * 60 * 1_000_000_000), // 30 days
+        expires_at: Some(time() + config().bounty_duration_ns),
         recipient: None,
+        funded_at: None,
+        paid_amount: 0,
+        fees_paid: 0,
+        platform_fees: 0,
+        transitions: vec![],
+        auto_distribution: None,
+        reminder_sent_at: None,
+        pending_transfers: vec![],
+        next_transfer_id: 0,
+        disputes: vec![],
+        contributions: vec![],
+        deposits: vec![],
     })
 }
 
-/* testing */
-// debug function to simulate funding
-#[update]
-pub fn mock_fund_bounty(artwork_id: u64, amount: u64) -> BountyResult {
-    // For testing only - simulates receiving funds
-    crate::ARTWORKS.with(|artworks| {
-        let mut artworks = artworks.borrow_mut();
-        if let Some(artwork) = artworks.iter_mut().find(|a| a.id == artwork_id) {
-            if let Some(ref mut bounty) = artwork.bounty {
-                bounty.actual_amount = amount;
-                return BountyResult::Success(format!("Mock funded with {} e8s", amount));
-            }
-        }
-        BountyResult::Error(BountyError::NotFound)
-    })
-}
-
-/// ✅ NEW - Simple balance check for testing (returns just the number)
-#[query]  
-pub async fn get_simple_bounty_balance(artwork_id: u64) -> u64 {
-    // For testing - just return a mock balance if artwork exists
-    if crate::ARTWORKS.with(|artworks| {
-        artworks.borrow().iter().any(|a| a.id == artwork_id)
-    }) {
-        100_000_000 // Return 1 ICP worth in e8s for testing
-    } else {
-        0
-    }
-}
-/* testing */
-
 /// ✅ NEW: Prepare escrow account and return funding info
 #[update]
 pub async fn prepare_bounty_account(
@@ -529,3 +2111,446 @@ pub async fn get_artwork_escrow_account(artwork_id: u64) -> Result<String, Strin
     }
 }
 
+
+/// A bounty escrow account known to the canister
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct EscrowAccount {
+    pub artwork_id: u64,
+    pub ledger: Principal,
+    pub account: Account,
+    pub account_id_hex: String, // legacy ICP AccountIdentifier of the same account
+    pub legacy: bool,           // derived with the pre-hash scheme
+    pub status: BountyStatus,
+}
+
+/// List the escrow accounts of all bounties
+#[query]
+pub fn list_escrow_accounts() -> Vec<EscrowAccount> {
+    crate::ARTWORKS.with(|artworks| {
+        artworks.borrow()
+            .iter()
+            .filter_map(|a| {
+                let bounty = a.bounty.as_ref()?;
+                let subaccount = stored_subaccount(a.id, a.author, bounty);
+                Some(EscrowAccount {
+                    artwork_id: a.id,
+                    ledger: bounty.ledger,
+                    account: escrow_icrc_account(subaccount),
+                    account_id_hex: AccountIdentifier::new(&crate::runtime::id(), &subaccount).to_hex(),
+                    legacy: subaccount == legacy_bounty_subaccount(a.id, a.author),
+                    status: bounty.status,
+                })
+            })
+            .collect()
+    })
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize)]
+pub struct EscrowMigrationReport {
+    pub pinned_legacy: u64,   // bounties without a recorded subaccount, now pinned to the legacy one
+    pub moved_to_hashed: u64, // empty, unfunded legacy escrows switched to the hashed scheme
+    pub kept_legacy: u64,     // legacy escrows that hold or held funds and stay where they are
+}
+
+/// Move bounties off the legacy subaccount scheme where that is safe (admin only).
+/// Escrows that already received funds keep their legacy subaccount.
+#[update]
+pub async fn migrate_escrow_subaccounts() -> Result<EscrowMigrationReport, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+
+    let mut report = EscrowMigrationReport::default();
+    let mut candidates: Vec<(u64, Principal, Principal, Subaccount)> = Vec::new();
+
+    crate::ARTWORKS.with(|artworks| {
+        for art in artworks.borrow_mut().iter_mut() {
+            let Some(bounty) = art.bounty.as_mut() else { continue };
+            let legacy = legacy_bounty_subaccount(art.id, art.author);
+            if bounty.subaccount.is_none() {
+                bounty.subaccount = Some(legacy);
+                report.pinned_legacy += 1;
+            }
+            if bounty.subaccount != Some(legacy) {
+                continue;
+            }
+            if bounty.status == BountyStatus::Prepared && bounty.actual_amount == 0 && bounty.funded_at.is_none() {
+                candidates.push((art.id, art.author, bounty.ledger, legacy));
+            } else {
+                report.kept_legacy += 1;
+            }
+        }
+    });
+
+    for (artwork_id, author, ledger, legacy) in candidates {
+        // Deposits may have been sent without a confirm_bounty_funding call
+        let balance = ledger_balance(ledger, escrow_icrc_account(legacy)).await.map_err(|e| format!("{:?}", e))?;
+
+        let moved = balance == 0 && with_bounty_mut(artwork_id, |bounty| {
+            let unchanged = bounty.subaccount == Some(legacy)
+                && bounty.status == BountyStatus::Prepared
+                && bounty.actual_amount == 0;
+            if unchanged {
+                let subaccount = generate_bounty_subaccount(artwork_id, author);
+                crate::escrow_audit::remember_escrow(BountyKey::Artwork(artwork_id), ledger, subaccount);
+                bounty.subaccount = Some(subaccount);
+            }
+            unchanged
+        }) == Some(true);
+
+        if moved {
+            report.moved_to_hashed += 1;
+        } else {
+            report.kept_legacy += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "mock-ledger"))]
+mod tests {
+    use super::*;
+    use crate::mock_ledger::mock_ledger_approve;
+    use crate::mock_ledger::testing::*;
+
+    const LEDGER: Principal = Principal::from_slice(&[7; 10]);
+
+    fn sponsor(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    fn funded_bounty(actual_amount: u64) -> Bounty {
+        Bounty {
+            ledger: LEDGER,
+            subaccount: None,
+            intended_amount: actual_amount,
+            actual_amount,
+            status: BountyStatus::Funded,
+            created_at: 0,
+            expires_at: None,
+            recipient: None,
+            funded_at: Some(0),
+            paid_amount: 0,
+            fees_paid: 0,
+            platform_fees: 0,
+            transitions: vec![],
+            auto_distribution: None,
+            reminder_sent_at: None,
+            pending_transfers: vec![],
+            next_transfer_id: 0,
+            disputes: vec![],
+            contributions: vec![],
+            deposits: vec![],
+        }
+    }
+
+    fn contribution(contributor: Principal, amount: u64) -> BountyContribution {
+        BountyContribution {
+            contributor,
+            amount,
+            contributed_at: 0,
+            last_block_index: None,
+            refunded: 0,
+            refunded_at: None,
+            refund_block: None,
+        }
+    }
+
+    #[test]
+    fn remaining_counts_payouts_fees_and_pending_transfers() {
+        let fee = crate::config::ledger_fee();
+        let mut bounty = funded_bounty(1_000_000);
+        bounty.paid_amount = 200_000;
+        bounty.fees_paid = fee;
+        bounty.platform_fees = 5_000;
+        bounty.pending_transfers.push(PendingTransfer {
+            id: 0,
+            kind: TransferKind::Refund,
+            to: sponsor(1),
+            amount: 100_000,
+            fee,
+            created_at_time: 0,
+            note: String::new(),
+            attempts: 1,
+            last_error: None,
+            platform_fee: 0,
+        });
+        assert_eq!(bounty.remaining(), 1_000_000 - 200_000 - 5_000 - 100_000 - 2 * fee);
+    }
+
+    #[test]
+    fn remaining_holds_back_the_platform_fee_of_pending_payouts() {
+        let fee = crate::config::ledger_fee();
+        let mut bounty = funded_bounty(1_000_000);
+        bounty.pending_transfers.push(PendingTransfer {
+            id: 0,
+            kind: TransferKind::CriticPayout { critique_id: Some(0) },
+            to: sponsor(1),
+            amount: 475_000,
+            fee,
+            created_at_time: 0,
+            note: String::new(),
+            attempts: 1,
+            last_error: None,
+            platform_fee: 25_000,
+        });
+        assert_eq!(bounty.remaining(), 500_000 - fee);
+    }
+
+    #[test]
+    fn award_percentages_split_what_is_left_after_fees() {
+        let fee = crate::config::ledger_fee();
+        let bounty = funded_bounty(1_000_000 + 2 * fee);
+        let amounts = award_amounts(&bounty, 0, &[AwardShare::Percent(60), AwardShare::Percent(40)]).unwrap();
+        assert_eq!(amounts, vec![600_000, 400_000]);
+    }
+
+    #[test]
+    fn award_respects_reserved_funds() {
+        let fee = crate::config::ledger_fee();
+        let bounty = funded_bounty(1_000_000 + fee);
+        assert!(award_amounts(&bounty, 0, &[AwardShare::Amount(1_000_000)]).is_ok());
+        assert!(matches!(
+            award_amounts(&bounty, 1, &[AwardShare::Amount(1_000_000)]),
+            Err(BountyError::InsufficientFunds)
+        ));
+        let amounts = award_amounts(&bounty, 500_000, &[AwardShare::Percent(100)]).unwrap();
+        assert_eq!(amounts, vec![500_000]);
+    }
+
+    #[test]
+    fn award_rejects_invalid_shares() {
+        let bounty = funded_bounty(1_000_000);
+        assert!(matches!(
+            award_amounts(&bounty, 0, &[AwardShare::Percent(70), AwardShare::Percent(40)]),
+            Err(BountyError::InvalidAmount)
+        ));
+        assert!(matches!(award_amounts(&bounty, 0, &[AwardShare::Amount(0)]), Err(BountyError::InvalidAmount)));
+        assert!(matches!(
+            award_amounts(&bounty, 0, &[AwardShare::Amount(u64::MAX), AwardShare::Amount(u64::MAX)]),
+            Err(BountyError::InsufficientFunds)
+        ));
+        assert!(matches!(
+            award_amounts(&funded_bounty(0), 0, &[AwardShare::Percent(100)]),
+            Err(BountyError::InsufficientFunds)
+        ));
+    }
+
+    #[test]
+    fn payable_amounts_include_the_fee_without_overflowing() {
+        let fee = crate::config::ledger_fee();
+        let bounty = funded_bounty(1_000_000);
+        assert!(bounty.check_payable(1_000_000 - fee, 0).is_ok());
+        assert!(matches!(bounty.check_payable(1_000_000, 0), Err(BountyError::InsufficientFunds)));
+        assert!(matches!(bounty.check_payable(u64::MAX, 0), Err(BountyError::InvalidAmount)));
+    }
+
+    #[test]
+    fn percentages_of_large_amounts_do_not_overflow() {
+        assert_eq!(percent_of(u64::MAX, 100), u64::MAX);
+        assert_eq!(percent_of(u64::MAX, 50), u64::MAX / 2);
+        assert_eq!(percent_of(1_000, 200), 1_000);
+    }
+
+    #[test]
+    fn refund_shares_are_pro_rata() {
+        let mut bounty = funded_bounty(1_000_000);
+        bounty.contributions = vec![contribution(sponsor(1), 250_000), contribution(sponsor(2), 250_000)];
+        // Half the escrow is gone; everyone gets back half of what they put in
+        let shares = sponsor_refund_shares(&bounty, 500_000);
+        assert_eq!(shares, vec![(sponsor(1), 125_000), (sponsor(2), 125_000)]);
+    }
+
+    #[test]
+    fn refund_shares_skip_refunded_sponsors() {
+        let mut bounty = funded_bounty(1_000_000);
+        let mut refunded = contribution(sponsor(1), 500_000);
+        refunded.refunded = 500_000;
+        refunded.refunded_at = Some(1);
+        bounty.contributions = vec![refunded, contribution(sponsor(2), 250_000)];
+        let shares = sponsor_refund_shares(&bounty, 500_000);
+        assert_eq!(shares, vec![(sponsor(2), 250_000)]);
+    }
+
+    #[test]
+    fn redacted_view_does_not_reveal_an_anonymous_critic() {
+        let critic = sponsor(3);
+        let mut bounty = funded_bounty(1_000_000);
+        bounty.recipient = Some(critic);
+        bounty.transitions.push(BountyTransition {
+            from: BountyStatus::Funded,
+            to: BountyStatus::PartiallyPaid,
+            timestamp: 0,
+            actor: critic,
+            amount: Some(100_000),
+            block_index: Some(1),
+            note: format!("paid critic {}", critic.to_text()),
+        });
+        bounty.deposits.push(BountyDeposit {
+            block_index: 2,
+            from: Some(AccountIdentifier::new(&critic, &DEFAULT_SUBACCOUNT).to_hex()),
+            amount: 1_000,
+            timestamp: 0,
+        });
+        let hidden: HashSet<Principal> = [critic].into_iter().collect();
+
+        let view = BountyView::redacted(&bounty, &hidden, false);
+        let text = format!("{:?}", view);
+        assert!(!text.contains(&critic.to_text()));
+        assert!(!text.contains(&AccountIdentifier::new(&critic, &DEFAULT_SUBACCOUNT).to_hex()));
+        let bytes = candid::encode_one(&view).unwrap();
+        assert!(!bytes.windows(critic.as_slice().len()).any(|w| w == critic.as_slice()));
+    }
+
+    #[test]
+    fn refund_outcome_message_covers_an_empty_escrow() {
+        let sent = RefundOutcome::Sent { amount: 42, block_index: 7 }.message(LEDGER);
+        assert!(sent.contains("Block index: 7"));
+        let empty = RefundOutcome::NothingLeft.message(LEDGER);
+        assert!(!empty.contains("Block index"));
+    }
+
+    fn critique_by(critic: Principal, artwork_id: u64) -> u64 {
+        crate::runtime::set_caller(critic);
+        crate::add_critique(artwork_id, "Strong composition".to_string(), false);
+        crate::ARTWORKS.with(|arts| {
+            arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.critiques.len() as u64 - 1)
+        }).unwrap()
+    }
+
+    fn bounty_of(artwork_id: u64) -> Bounty {
+        with_bounty_mut(artwork_id, |b| b.clone()).unwrap()
+    }
+
+    fn escrow_balance_of(ledger: Principal, artwork_id: u64) -> u64 {
+        let account = crate::ARTWORKS.with(|arts| {
+            arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| {
+                bounty_escrow_account(a.id, a.author, a.bounty.as_ref().unwrap())
+            })
+        }).unwrap();
+        balance_of(ledger, account)
+    }
+
+    #[test]
+    fn funding_with_an_approval_fills_the_escrow() {
+        let ledger = use_mock_ledger();
+        let fee = crate::tokens::token_fee(ledger);
+        let author = sponsor(1);
+        mint(ledger, author, 2_000_000);
+        let artwork_id = bounty_artwork(author, 1_000_000);
+
+        assert!(matches!(fund(ledger, author, artwork_id, 1_000_000), BountyResult::Success(_)));
+        let bounty = bounty_of(artwork_id);
+        assert_eq!(bounty.status, BountyStatus::Funded);
+        assert_eq!(bounty.actual_amount, 1_000_000);
+        assert_eq!(bounty.deposits.len(), 1);
+        assert_eq!(escrow_balance_of(ledger, artwork_id), 1_000_000);
+        // One fee for the approval and one for the transfer
+        assert_eq!(balance_of(ledger, Account::from(author)), 1_000_000 - 2 * fee);
+    }
+
+    #[test]
+    fn funding_is_not_recorded_when_the_ledger_call_fails() {
+        let ledger = use_mock_ledger();
+        let author = sponsor(1);
+        mint(ledger, author, 2_000_000);
+        let artwork_id = bounty_artwork(author, 1_000_000);
+
+        set_caller(author);
+        assert!(mock_ledger_approve(ledger, 2_000_000).is_ok());
+        fail_next_calls(1);
+        assert!(matches!(
+            block_on(fund_bounty_with_approval(artwork_id, 1_000_000)),
+            BountyResult::Error(BountyError::TransferFailed(_))
+        ));
+        let bounty = bounty_of(artwork_id);
+        assert_eq!(bounty.status, BountyStatus::Prepared);
+        assert_eq!(bounty.actual_amount, 0);
+        assert_eq!(escrow_balance_of(ledger, artwork_id), 0);
+
+        assert!(matches!(block_on(fund_bounty_with_approval(artwork_id, 1_000_000)), BountyResult::Success(_)));
+        assert_eq!(bounty_of(artwork_id).status, BountyStatus::Funded);
+    }
+
+    #[test]
+    fn award_pays_each_critic_from_the_escrow() {
+        let ledger = use_mock_ledger();
+        let fee = crate::tokens::token_fee(ledger);
+        let author = sponsor(1);
+        mint(ledger, author, 2_000_000);
+        let artwork_id = bounty_artwork(author, 1_000_000 + 2 * fee);
+        assert!(matches!(fund(ledger, author, artwork_id, 1_000_000 + 2 * fee), BountyResult::Success(_)));
+        let first = critique_by(sponsor(2), artwork_id);
+        let second = critique_by(sponsor(3), artwork_id);
+
+        set_caller(author);
+        let payouts = block_on(award_bounty(artwork_id, vec![
+            CritiqueAward { critique_id: first, share: AwardShare::Percent(60) },
+            CritiqueAward { critique_id: second, share: AwardShare::Percent(40) },
+        ])).unwrap();
+        assert!(payouts.iter().all(|p| p.block_index.is_some() && p.error.is_none()));
+        assert_eq!(balance_of(ledger, Account::from(sponsor(2))), 600_000);
+        assert_eq!(balance_of(ledger, Account::from(sponsor(3))), 400_000);
+        assert_eq!(escrow_balance_of(ledger, artwork_id), 0);
+
+        let bounty = bounty_of(artwork_id);
+        assert_eq!(bounty.status, BountyStatus::Paid);
+        assert_eq!(bounty.remaining(), 0);
+        // A critique is only paid once
+        assert!(matches!(
+            block_on(award_bounty(artwork_id, vec![CritiqueAward { critique_id: first, share: AwardShare::Amount(1) }])),
+            Err(BountyError::AlreadyReleased)
+        ));
+    }
+
+    #[test]
+    fn award_with_an_unknown_outcome_stays_pending_until_reconciled() {
+        let ledger = use_mock_ledger();
+        let author = sponsor(1);
+        mint(ledger, author, 2_000_000);
+        let artwork_id = bounty_artwork(author, 1_000_000);
+        assert!(matches!(fund(ledger, author, artwork_id, 1_000_000), BountyResult::Success(_)));
+        let critique_id = critique_by(sponsor(2), artwork_id);
+
+        set_caller(author);
+        fail_next_calls(1);
+        let payouts = block_on(award_bounty(artwork_id, vec![
+            CritiqueAward { critique_id, share: AwardShare::Amount(500_000) },
+        ])).unwrap();
+        assert!(payouts[0].error.as_deref().is_some_and(|e| e.starts_with("TransferPending")));
+        assert_eq!(balance_of(ledger, Account::from(sponsor(2))), 0);
+        assert_eq!(bounty_of(artwork_id).pending_transfers.len(), 1);
+
+        // The escrow is held for the payout, and the critique cannot be awarded twice
+        assert!(matches!(
+            block_on(award_bounty(artwork_id, vec![CritiqueAward { critique_id, share: AwardShare::Amount(1) }])),
+            Err(BountyError::AlreadyReleased)
+        ));
+
+        assert!(block_on(reconcile_pending_transfers(artwork_id)).unwrap().is_empty());
+        assert_eq!(balance_of(ledger, Account::from(sponsor(2))), 500_000);
+        let bounty = bounty_of(artwork_id);
+        assert_eq!(bounty.paid_amount, 500_000);
+        assert_eq!(bounty.status, BountyStatus::PartiallyPaid);
+    }
+
+    #[test]
+    fn award_is_rejected_when_the_shares_do_not_fit() {
+        let ledger = use_mock_ledger();
+        let fee = crate::tokens::token_fee(ledger);
+        let author = sponsor(1);
+        mint(ledger, author, 2_000_000);
+        let artwork_id = bounty_artwork(author, 1_000_000);
+        assert!(matches!(fund(ledger, author, artwork_id, 1_000_000), BountyResult::Success(_)));
+        let critique_id = critique_by(sponsor(2), artwork_id);
+
+        set_caller(author);
+        assert!(matches!(
+            block_on(award_bounty(artwork_id, vec![CritiqueAward { critique_id, share: AwardShare::Amount(1_000_000 - fee + 1) }])),
+            Err(BountyError::InsufficientFunds)
+        ));
+        assert_eq!(escrow_balance_of(ledger, artwork_id), 1_000_000);
+        assert!(bounty_of(artwork_id).pending_transfers.is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use crate::bounty::{AwardPayout, BountyError, BountyStatus};
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ClaimStatus {
+    Pending,   // waiting for the artist
+    Escalated, // artist did not answer in time or the payout failed, waiting for an admin
+    Approved,  // accepted, payout not completed yet
+    Rejected,
+    Paid,
+}
+
+impl ClaimStatus {
+    /// The claim may still be paid from the escrow
+    fn is_unresolved(self) -> bool {
+        matches!(self, ClaimStatus::Pending | ClaimStatus::Escalated | ClaimStatus::Approved)
+    }
+}
+
+/// A critic's request to be paid from a bounty for one of their critiques
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyClaim {
+    pub id: u64,
+    pub artwork_id: u64,
+    pub critique_id: u64,
+    pub critic: Principal,
+    pub amount: u64, // requested amount in the bounty token's smallest unit
+    pub message: String,
+    pub status: ClaimStatus,
+    pub created_at: u64,
+    pub respond_by: u64, // escalates to arbitration if still pending after this
+    pub decided_at: Option<u64>,
+    pub decided_by: Option<Principal>,
+    pub reason: Option<String>,
+    pub payout_block: Option<u64>,
+    pub payout_error: Option<String>,
+}
+
+impl BountyClaim {
+    /// Copy that does not reveal the critic if they are in `hidden`
+    pub(crate) fn redacted(&self, hidden: &HashSet<Principal>) -> BountyClaim {
+        let mut claim = self.clone();
+        if hidden.contains(&claim.critic) {
+            claim.critic = Principal::anonymous();
+        }
+        claim
+    }
+}
+
+thread_local! {
+    static CLAIMS: RefCell<Vec<BountyClaim>> = const { RefCell::new(Vec::new()) };
+    static CLAIM_ID: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Upgrade snapshot of bounty claims
+#[derive(CandidType, Deserialize)]
+pub(crate) struct ClaimState {
+    claims: Vec<BountyClaim>,
+    next_id: u64,
+}
+
+pub(crate) fn save_state() -> ClaimState {
+    ClaimState {
+        claims: CLAIMS.with(|s| s.take()),
+        next_id: CLAIM_ID.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: ClaimState) {
+    CLAIMS.with(|s| *s.borrow_mut() = state.claims);
+    CLAIM_ID.with(|s| *s.borrow_mut() = state.next_id);
+}
+
+fn with_claim_mut<R>(claim_id: u64, f: impl FnOnce(&mut BountyClaim) -> R) -> Option<R> {
+    CLAIMS.with(|claims| claims.borrow_mut().iter_mut().find(|c| c.id == claim_id).map(f))
+}
+
+fn get_claim(claim_id: u64) -> Result<BountyClaim, BountyError> {
+    CLAIMS.with(|claims| claims.borrow().iter().find(|c| c.id == claim_id).cloned())
+        .ok_or(BountyError::NotFound)
+}
+
+/// `(critique_id, amount)` of the claims on an artwork's bounty that may
+/// still be paid. Their funds are held back from other payouts and refunds.
+pub(crate) fn unresolved_claims(artwork_id: u64) -> Vec<(u64, u64)> {
+    CLAIMS.with(|claims| {
+        claims.borrow()
+            .iter()
+            .filter(|c| c.artwork_id == artwork_id && c.status.is_unresolved())
+            .map(|c| (c.critique_id, c.amount))
+            .collect()
+    })
+}
+
+/// A claim on the critique may still be paid, or already was
+pub(crate) fn is_claimed(artwork_id: u64, critique_id: u64) -> bool {
+    CLAIMS.with(|claims| {
+        claims.borrow().iter().any(|c| {
+            c.artwork_id == artwork_id
+                && c.critique_id == critique_id
+                && (c.status.is_unresolved() || c.status == ClaimStatus::Paid)
+        })
+    })
+}
+
+/// Mark the approved claims on a critique as paid once its payout settled,
+/// e.g. after a transfer with unknown outcome was reconciled
+pub(crate) fn record_claim_payout(artwork_id: u64, critique_id: u64, block_index: Option<u64>) {
+    CLAIMS.with(|claims| {
+        for claim in claims.borrow_mut().iter_mut() {
+            if claim.artwork_id == artwork_id && claim.critique_id == critique_id && claim.status == ClaimStatus::Approved {
+                claim.status = ClaimStatus::Paid;
+                claim.payout_block = block_index;
+                claim.payout_error = None;
+            }
+        }
+    });
+}
+
+fn artwork_author(artwork_id: u64) -> Option<Principal> {
+    crate::ARTWORKS.with(|arts| arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.author))
+}
+
+/// Claim part of a bounty for one of the caller's critiques. The artist has
+/// until `respond_by` to approve or reject it.
+#[update]
+pub fn claim_bounty(artwork_id: u64, critique_id: u64, amount: u64, message: String) -> Result<BountyClaim, BountyError> {
+    let critic = caller();
+
+    let (author, title, ledger) = crate::ARTWORKS.with(|arts| {
+        let mut arts = arts.borrow_mut();
+        let artwork = arts.iter_mut().find(|a| a.id == artwork_id).ok_or(BountyError::NotFound)?;
+        let critique = artwork.critiques
+            .iter()
+            .find(|c| c.id == critique_id)
+            .ok_or(BountyError::NotFound)?;
+        if critique.critic != critic || critic == artwork.author {
+            return Err(BountyError::NotAuthorized);
+        }
+        if critique.reward.is_some() {
+            return Err(BountyError::AlreadyReleased);
+        }
+
+        let bounty = artwork.bounty.as_mut().ok_or(BountyError::NotFound)?;
+        bounty.expire_if_due();
+        match bounty.status {
+            BountyStatus::Prepared => return Err(BountyError::NotFunded),
+            BountyStatus::Expired => return Err(BountyError::Expired),
+            status if !status.is_payable() => return Err(BountyError::AlreadyReleased),
+            _ => {}
+        }
+        if bounty.is_disputed(critique_id) {
+            return Err(BountyError::InvalidTransition("the critique is under dispute".to_string()));
+        }
+        if amount == 0 {
+            return Err(BountyError::InvalidAmount);
+        }
+        bounty.check_payable(amount, crate::bounty::reserved_for_critics(artwork_id, bounty, None))?;
+        Ok((artwork.author, artwork.title.clone(), bounty.ledger))
+    })?;
+
+    let open_claim = CLAIMS.with(|claims| {
+        claims.borrow().iter().any(|c| {
+            c.artwork_id == artwork_id && c.critique_id == critique_id && c.status.is_unresolved()
+        })
+    });
+    if open_claim {
+        return Err(BountyError::AlreadyReleased);
+    }
+
+    let id = CLAIM_ID.with(|n| {
+        let mut n = n.borrow_mut();
+        *n += 1;
+        *n
+    });
+    let now = time();
+    let claim = BountyClaim {
+        id,
+        artwork_id,
+        critique_id,
+        critic,
+        amount,
+        message,
+        status: ClaimStatus::Pending,
+        created_at: now,
+        respond_by: now + crate::bounty_jobs::get_bounty_job_config().claim_response_ns,
+        decided_at: None,
+        decided_by: None,
+        reason: None,
+        payout_block: None,
+        payout_error: None,
+    };
+    CLAIMS.with(|claims| claims.borrow_mut().push(claim.clone()));
+
+    crate::notifications::notify(author, Some(artwork_id), format!(
+        "A critic claimed {} from your bounty on \"{}\". Approve or reject the claim before it goes to arbitration.",
+        crate::tokens::format_amount(ledger, amount),
+        title
+    ));
+    Ok(claim)
+}
+
+// Record the decision and, for approvals, pay the critic
+async fn decide(claim_id: u64, approve: bool, reason: Option<String>) -> Result<BountyClaim, BountyError> {
+    let decider = caller();
+    let claim = get_claim(claim_id)?;
+    let disputed = crate::bounty::artwork_bounty(claim.artwork_id).is_some_and(|b| b.is_disputed(claim.critique_id));
+    if approve && disputed {
+        return Err(BountyError::InvalidTransition("the critique is under dispute".to_string()));
+    }
+    with_claim_mut(claim_id, |claim| {
+        claim.status = if approve { ClaimStatus::Approved } else { ClaimStatus::Rejected };
+        claim.decided_at = Some(time());
+        claim.decided_by = Some(decider);
+        claim.reason = reason;
+    });
+
+    let claim = get_claim(claim_id)?;
+    if approve {
+        let result = crate::bounty::pay_critique(claim.artwork_id, claim.critique_id, claim.amount).await;
+        with_claim_mut(claim_id, |claim| match result {
+            Ok(AwardPayout { block_index, .. }) => {
+                claim.status = ClaimStatus::Paid;
+                claim.payout_block = block_index;
+                claim.payout_error = None;
+            }
+            // Stays approved until the transfer is reconciled
+            Err(e @ BountyError::TransferPending(_)) => claim.payout_error = Some(format!("{:?}", e)),
+            // Nothing was sent: an admin can approve again to retry, or reject it
+            Err(e) => {
+                claim.status = ClaimStatus::Escalated;
+                claim.payout_error = Some(format!("{:?}", e));
+            }
+        });
+    }
+
+    let claim = get_claim(claim_id)?;
+    let outcome = match claim.status {
+        ClaimStatus::Paid => "was approved and paid".to_string(),
+        ClaimStatus::Approved => "was approved; the payout is still pending".to_string(),
+        ClaimStatus::Escalated => "was approved, but the payout failed. It was passed to an admin".to_string(),
+        _ => format!("was rejected: {}", claim.reason.clone().unwrap_or_default()),
+    };
+    crate::notifications::notify(claim.critic, Some(claim.artwork_id), format!("Your bounty claim #{} {}.", claim.id, outcome));
+    Ok(claim)
+}
+
+/// Approve a claim on the caller's bounty and pay the critic (artist only).
+/// Also retries the payout of an approved claim that has not been paid yet.
+#[update]
+pub async fn approve_bounty_claim(claim_id: u64) -> Result<BountyClaim, BountyError> {
+    let claim = get_claim(claim_id)?;
+    if artwork_author(claim.artwork_id) != Some(caller()) {
+        return Err(BountyError::NotAuthorized);
+    }
+    if !matches!(claim.status, ClaimStatus::Pending | ClaimStatus::Escalated | ClaimStatus::Approved) {
+        return Err(BountyError::InvalidTransition(format!("claim is {:?}", claim.status)));
+    }
+    decide(claim_id, true, claim.reason).await
+}
+
+/// Reject a pending claim on the caller's bounty (artist only). A reason is required.
+#[update]
+pub async fn reject_bounty_claim(claim_id: u64, reason: String) -> Result<BountyClaim, BountyError> {
+    let claim = get_claim(claim_id)?;
+    if artwork_author(claim.artwork_id) != Some(caller()) {
+        return Err(BountyError::NotAuthorized);
+    }
+    if claim.status != ClaimStatus::Pending {
+        return Err(BountyError::InvalidTransition(format!("claim is {:?}", claim.status)));
+    }
+    if reason.trim().is_empty() {
+        return Err(BountyError::InvalidTransition("a reason is required".to_string()));
+    }
+    decide(claim_id, false, Some(reason)).await
+}
+
+/// Decide an escalated claim the artist did not answer in time, or retry or
+/// reject one whose payout failed (admin only)
+#[update]
+pub async fn arbitrate_bounty_claim(claim_id: u64, approve: bool, reason: String) -> Result<BountyClaim, BountyError> {
+    if !crate::is_admin(&caller()) {
+        return Err(BountyError::NotAuthorized);
+    }
+    let claim = get_claim(claim_id)?;
+    if claim.status != ClaimStatus::Escalated {
+        return Err(BountyError::InvalidTransition(format!("claim is {:?}", claim.status)));
+    }
+    if reason.trim().is_empty() {
+        return Err(BountyError::InvalidTransition("a reason is required".to_string()));
+    }
+    decide(claim_id, approve, Some(reason)).await
+}
+
+/// Move pending claims past their deadline to arbitration. Run by the bounty sweep.
+pub(crate) fn escalate_overdue_claims(now: u64) {
+    let escalated: Vec<BountyClaim> = CLAIMS.with(|claims| {
+        claims.borrow_mut()
+            .iter_mut()
+            .filter(|c| c.status == ClaimStatus::Pending && now > c.respond_by)
+            .map(|c| {
+                c.status = ClaimStatus::Escalated;
+                c.clone()
+            })
+            .collect()
+    });
+
+    for claim in escalated {
+        crate::notifications::notify(claim.critic, Some(claim.artwork_id), format!(
+            "The artist did not answer your bounty claim #{} in time. It was passed to an admin for arbitration.",
+            claim.id
+        ));
+        if let Some(author) = artwork_author(claim.artwork_id) {
+            crate::notifications::notify(author, Some(claim.artwork_id), format!(
+                "Bounty claim #{} was passed to arbitration because it was not answered in time. You can still approve it.",
+                claim.id
+            ));
+        }
+    }
+}
+
+/// Claims on an artwork's bounty. The artist and admins see all of them, critics
+/// their own. Only admins see which critic made an anonymous critique's claim.
+#[query]
+pub fn get_bounty_claims(artwork_id: u64) -> Vec<BountyClaim> {
+    let requester = caller();
+    let is_admin = crate::is_admin(&requester);
+    let full_access = artwork_author(artwork_id) == Some(requester) || is_admin;
+    let hidden: HashSet<Principal> = if is_admin {
+        HashSet::new()
+    } else {
+        crate::ARTWORKS.with(|arts| {
+            arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.anonymous_principals())
+        }).unwrap_or_default()
+    };
+    CLAIMS.with(|claims| {
+        claims.borrow()
+            .iter()
+            .filter(|c| c.artwork_id == artwork_id && (full_access || c.critic == requester))
+            .map(|c| if c.critic == requester { c.clone() } else { c.redacted(&hidden) })
+            .collect()
+    })
+}
+
+/// Claims submitted by the caller, newest first
+#[query]
+pub fn get_my_bounty_claims() -> Vec<BountyClaim> {
+    let critic = caller();
+    CLAIMS.with(|claims| claims.borrow().iter().rev().filter(|c| c.critic == critic).cloned().collect())
+}
+
+/// Claims waiting for arbitration (admin only)
+#[query]
+pub fn get_escalated_claims() -> Result<Vec<BountyClaim>, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    Ok(CLAIMS.with(|claims| {
+        claims.borrow().iter().filter(|c| c.status == ClaimStatus::Escalated).cloned().collect()
+    }))
+}
@@ -0,0 +1,421 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use ic_cdk_timers::TimerId;
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::bounty::{BountyKey, BountyStatus, RefundOutcome};
+
+const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+
+/// Settings for the background bounty jobs (admin-controlled)
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyJobConfig {
+    /// How often the background sweep checks all bounties
+    pub sweep_interval_ns: u64,
+    /// Remind authors this long before their bounty expires (0 disables reminders)
+    pub reminder_before_ns: u64,
+    /// Return the remaining escrow to the author automatically once a funded bounty expires
+    pub auto_refund: bool,
+    /// Artists must answer a bounty claim within this long, or it goes to arbitration
+    pub claim_response_ns: u64,
+}
+
+impl Default for BountyJobConfig {
+    fn default() -> Self {
+        Self {
+            sweep_interval_ns: HOUR_NS,
+            reminder_before_ns: 48 * HOUR_NS,
+            auto_refund: false,
+            claim_response_ns: 7 * 24 * HOUR_NS,
+        }
+    }
+}
+
+thread_local! {
+    static JOB_CONFIG: RefCell<BountyJobConfig> = RefCell::new(BountyJobConfig::default());
+    static SWEEP_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+    static SWEEP_RUNNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+/// Upgrade snapshot of the bounty job settings
+#[derive(CandidType, Deserialize)]
+pub(crate) struct BountyJobState {
+    config: BountyJobConfig,
+}
+
+pub(crate) fn save_state() -> BountyJobState {
+    BountyJobState {
+        config: JOB_CONFIG.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: BountyJobState) {
+    JOB_CONFIG.with(|s| *s.borrow_mut() = state.config);
+}
+
+// Clears the running flag when the sweep ends, including on trap
+struct SweepGuard;
+
+impl Drop for SweepGuard {
+    fn drop(&mut self) {
+        SWEEP_RUNNING.with(|r| *r.borrow_mut() = false);
+    }
+}
+
+/// Arm the periodic sweep and the one-shot auto-distribution timers.
+/// Called from `init` and `post_upgrade`, since timers do not survive upgrades.
+pub(crate) fn arm_bounty_jobs() {
+    arm_sweep_timer();
+
+    let pending: Vec<(u64, u64)> = crate::ARTWORKS.with(|arts| {
+        arts.borrow()
+            .iter()
+            .filter_map(|a| {
+                let bounty = a.bounty.as_ref()?;
+                let policy = bounty.auto_distribution.as_ref()?;
+                if policy.executed_at.is_some() {
+                    return None;
+                }
+                bounty.expires_at.map(|expires_at| (a.id, expires_at))
+            })
+            .collect()
+    });
+    for (artwork_id, expires_at) in pending {
+        crate::bounty::schedule_auto_distribution(artwork_id, expires_at);
+    }
+}
+
+fn arm_sweep_timer() {
+    let interval = JOB_CONFIG.with(|c| c.borrow().sweep_interval_ns);
+    if let Some(old) = SWEEP_TIMER.with(|t| t.borrow_mut().take()) {
+        ic_cdk_timers::clear_timer(old);
+    }
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_nanos(interval), || {
+        ic_cdk::spawn(sweep_bounties());
+    });
+    SWEEP_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+}
+
+/// Pick up escrow deposits from new ICP ledger blocks, mark expired bounties and critique requests,
+/// send expiry reminders, escalate unanswered claims,
+/// rule disputes whose voting ended, retry transfers with unknown outcome, run pending auto-distributions and,
+/// if enabled, refund the remaining escrow of expired bounties. Escrows of
+/// deleted artworks are always refunded, and the artworks removed once settled.
+async fn sweep_bounties() {
+    // Skip this tick if the previous sweep is still waiting on the ledger
+    if SWEEP_RUNNING.with(|r| r.replace(true)) {
+        return;
+    }
+    let _guard = SweepGuard;
+
+    // Record deposits first, so bounties funded by plain transfers are seen as funded below
+    if !crate::config::is_testing() {
+        if let Err(e) = crate::deposits::scan_deposits().await {
+            ic_cdk::println!("Deposit scan failed: {}", e);
+        }
+    }
+
+    let config = JOB_CONFIG.with(|c| c.borrow().clone());
+    let now = time();
+    let mut reminders: Vec<(Principal, u64, String, u64)> = Vec::new();
+    let mut reconciliations: Vec<BountyKey> = Vec::new();
+    let mut distributions: Vec<u64> = Vec::new();
+    let mut refunds: Vec<(BountyKey, Principal, Principal)> = Vec::new();
+
+    crate::ARTWORKS.with(|arts| {
+        for art in arts.borrow_mut().iter_mut() {
+            let Some(bounty) = art.bounty.as_mut() else { continue };
+            bounty.expire_if_due();
+
+            let open = matches!(
+                bounty.status,
+                BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid
+            );
+            if let (true, Some(expires_at), None) = (open, bounty.expires_at, bounty.reminder_sent_at) {
+                if config.reminder_before_ns > 0 && expires_at.saturating_sub(now) <= config.reminder_before_ns {
+                    bounty.reminder_sent_at = Some(now);
+                    reminders.push((art.author, art.id, art.title.clone(), expires_at));
+                }
+            }
+
+            if !bounty.pending_transfers.is_empty() {
+                reconciliations.push(BountyKey::Artwork(art.id));
+                continue;
+            }
+
+            // Deleted artworks always get their escrow returned, so they can be
+            // removed. The refund reads the ledger, so unrecorded funds are included.
+            let deleted = art.deleted_at.is_some();
+            if bounty.status == BountyStatus::Expired {
+                let distribution_pending = bounty.auto_distribution.as_ref().is_some_and(|d| d.executed_at.is_none());
+                if distribution_pending {
+                    distributions.push(art.id);
+                } else if (config.auto_refund && bounty.funded_at.is_some()) || deleted {
+                    refunds.push((BountyKey::Artwork(art.id), art.author, bounty.ledger));
+                }
+            } else if deleted && bounty.status == BountyStatus::Paid {
+                refunds.push((BountyKey::Artwork(art.id), art.author, bounty.ledger));
+            }
+        }
+    });
+
+    let (request_reconciliations, request_refunds) = crate::critique_requests::due_request_jobs(config.auto_refund);
+    reconciliations.extend(request_reconciliations.into_iter().map(BountyKey::Request));
+    refunds.extend(request_refunds.into_iter().map(|(id, author, ledger)| (BountyKey::Request(id), author, ledger)));
+
+    crate::bounty_claims::escalate_overdue_claims(now);
+    crate::disputes::settle_disputes(now).await;
+
+    for (author, artwork_id, title, expires_at) in reminders {
+        let hours_left = expires_at.saturating_sub(now) / HOUR_NS;
+        crate::notifications::notify(author, Some(artwork_id), format!(
+            "Your bounty on \"{}\" expires in about {} hours. Reward the critiques you found helpful before it closes.",
+            title, hours_left
+        ));
+    }
+
+    for key in reconciliations {
+        if let Err(e) = crate::bounty::reconcile_pending_transfers(key).await {
+            ic_cdk::println!("Reconciling bounty {:?} failed: {:?}", key, e);
+        }
+    }
+
+    for artwork_id in distributions {
+        if let Err(e) = crate::bounty::run_auto_distribution(artwork_id).await {
+            ic_cdk::println!("Auto-distribution for bounty {} failed: {:?}", artwork_id, e);
+        }
+    }
+
+    for (key, author, ledger) in refunds {
+        refund_expired_bounty(key, author, ledger).await;
+    }
+
+    purge_deleted_artworks().await;
+}
+
+async fn refund_expired_bounty(key: BountyKey, author: Principal, ledger: Principal) {
+    let Ok(_lock) = crate::bounty::BountyLock::acquire(key) else { return };
+    let still_due = crate::bounty::with_bounty_mut(key, |b| {
+        matches!(b.status, BountyStatus::Expired | BountyStatus::Paid)
+            && b.pending_transfers.is_empty()
+            && !b.has_open_dispute()
+    });
+    // Claims that may still be paid keep their funds in escrow
+    if key.artwork_id().is_some_and(|id| !crate::bounty_claims::unresolved_claims(id).is_empty()) {
+        return;
+    }
+    if still_due != Some(true) {
+        return;
+    }
+
+    let balance = match crate::bounty::escrow_balance(key).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => {
+            ic_cdk::println!("Balance check for bounty {:?} failed: {:?}", key, e);
+            return;
+        }
+    };
+    let fee = crate::tokens::token_fee(ledger);
+    if balance <= fee {
+        return;
+    }
+
+    match crate::bounty::refund_escrow(key, author, balance, "automatic refund after expiry").await {
+        Ok(RefundOutcome::Sent { amount, block_index }) => crate::notifications::notify(author, key.artwork_id(), format!(
+            "Your expired bounty was refunded: {} returned. {}.",
+            crate::tokens::format_amount(ledger, amount),
+            crate::bounty::block_note(block_index)
+        )),
+        Ok(RefundOutcome::NothingLeft) => crate::notifications::notify(author, key.artwork_id(),
+            "Your expired bounty was closed. Its remaining funds went back to its sponsors.".to_string(),
+        ),
+        Err(e) => ic_cdk::println!("Automatic refund for bounty {:?} failed: {:?}", key, e),
+    }
+}
+
+// Nothing more can leave the escrow of a deleted artwork through the bounty
+fn nothing_owed(artwork_id: u64) -> bool {
+    let settled = crate::ARTWORKS.with(|arts| {
+        arts.borrow().iter().find(|a| a.id == artwork_id).is_some_and(|a| {
+            a.deleted_at.is_some()
+                && a.bounty.as_ref().is_none_or(|b| b.pending_transfers.is_empty() && !b.has_open_dispute())
+        })
+    });
+    settled && crate::bounty_claims::unresolved_claims(artwork_id).is_empty()
+}
+
+// Remove deleted artworks once nothing is owed from their escrow and the
+// ledger shows it empty, so funds the records missed are not left behind
+async fn purge_deleted_artworks() {
+    let deleted: Vec<u64> = crate::ARTWORKS.with(|arts| {
+        arts.borrow().iter().filter(|a| a.deleted_at.is_some()).map(|a| a.id).collect()
+    });
+
+    for artwork_id in deleted {
+        let Ok(_lock) = crate::bounty::BountyLock::acquire(artwork_id) else { continue };
+        if !nothing_owed(artwork_id) {
+            continue;
+        }
+        let has_bounty = crate::bounty::with_bounty_mut(artwork_id, |_| ()).is_some();
+        if has_bounty {
+            match crate::bounty::escrow_balance(artwork_id).await {
+                Ok(balance) if balance.e8s <= balance.token.fee => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    ic_cdk::println!("Balance check for deleted artwork {} failed: {:?}", artwork_id, e);
+                    continue;
+                }
+            }
+        }
+        // Checked again, since a transfer or claim may have started during the call
+        if nothing_owed(artwork_id) {
+            crate::ARTWORKS.with(|arts| arts.borrow_mut().retain(|a| a.id != artwork_id));
+        }
+    }
+}
+
+/// Update the background bounty job settings (admin only)
+#[update]
+pub fn configure_bounty_jobs(config: BountyJobConfig) -> Result<String, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if config.sweep_interval_ns < 60 * 1_000_000_000 {
+        return Err("Sweep interval must be at least one minute".to_string());
+    }
+
+    JOB_CONFIG.with(|c| *c.borrow_mut() = config);
+    arm_sweep_timer();
+    Ok("Bounty job configuration updated".to_string())
+}
+
+#[query]
+pub fn get_bounty_job_config() -> BountyJobConfig {
+    JOB_CONFIG.with(|c| c.borrow().clone())
+}
+
+#[cfg(all(test, feature = "mock-ledger"))]
+mod tests {
+    use super::*;
+    use crate::bounty::{award_bounty, AwardShare, CritiqueAward};
+    use crate::mock_ledger::testing::*;
+    use icrc_ledger_types::icrc1::account::Account;
+
+    const DAY_NS: u64 = 24 * HOUR_NS;
+
+    fn user(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    fn escrow(ledger: Principal, artwork_id: u64) -> u64 {
+        let account = crate::ARTWORKS.with(|arts| {
+            arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| {
+                crate::bounty::bounty_escrow_account(a.id, a.author, a.bounty.as_ref().unwrap())
+            })
+        }).unwrap();
+        balance_of(ledger, account)
+    }
+
+    fn status(artwork_id: u64) -> BountyStatus {
+        crate::bounty::with_bounty_mut(artwork_id, |b| b.status).unwrap()
+    }
+
+    // A funded bounty on a new artwork by user 1, past its deadline
+    fn expired_bounty(ledger: Principal, amount: u64) -> u64 {
+        mint(ledger, user(1), 2 * amount);
+        let artwork_id = bounty_artwork(user(1), amount);
+        assert!(matches!(fund(ledger, user(1), artwork_id, amount), crate::bounty::BountyResult::Success(_)));
+        advance_time(31 * DAY_NS);
+        crate::bounty::with_bounty_mut(artwork_id, |b| b.expire_if_due());
+        assert_eq!(status(artwork_id), BountyStatus::Expired);
+        artwork_id
+    }
+
+    #[test]
+    fn expired_bounty_is_refunded_to_the_author() {
+        let ledger = use_mock_ledger();
+        let fee = crate::tokens::token_fee(ledger);
+        let artwork_id = expired_bounty(ledger, 1_000_000);
+        let before = balance_of(ledger, Account::from(user(1)));
+
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 0);
+        assert_eq!(balance_of(ledger, Account::from(user(1))), before + 1_000_000 - fee);
+        assert_eq!(status(artwork_id), BountyStatus::Refunded);
+    }
+
+    #[test]
+    fn refund_is_retried_after_a_failed_balance_check() {
+        let ledger = use_mock_ledger();
+        let artwork_id = expired_bounty(ledger, 1_000_000);
+
+        fail_next_calls(1);
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 1_000_000);
+        assert_eq!(status(artwork_id), BountyStatus::Expired);
+
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 0);
+        assert_eq!(status(artwork_id), BountyStatus::Refunded);
+    }
+
+    #[test]
+    fn refund_waits_for_a_payout_with_unknown_outcome() {
+        let ledger = use_mock_ledger();
+        mint(ledger, user(1), 2_000_000);
+        let artwork_id = bounty_artwork(user(1), 1_000_000);
+        assert!(matches!(fund(ledger, user(1), artwork_id, 1_000_000), crate::bounty::BountyResult::Success(_)));
+        set_caller(user(2));
+        crate::add_critique(artwork_id, "Strong composition".to_string(), false);
+
+        set_caller(user(1));
+        fail_next_calls(1);
+        let payouts = block_on(award_bounty(artwork_id, vec![
+            CritiqueAward { critique_id: 0, share: AwardShare::Amount(400_000) },
+        ])).unwrap();
+        assert!(payouts[0].error.is_some());
+        advance_time(31 * DAY_NS);
+        crate::bounty::with_bounty_mut(artwork_id, |b| b.expire_if_due());
+
+        // The escrow must still cover the payout, so nothing is refunded yet
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 1_000_000);
+
+        // The ledger no longer deduplicates the payout, so it is not re-sent automatically
+        let pending = block_on(crate::bounty::reconcile_pending_transfers(artwork_id)).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(balance_of(ledger, Account::from(user(2))), 0);
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 1_000_000);
+    }
+
+    #[test]
+    fn refund_follows_a_reconciled_payout() {
+        let ledger = use_mock_ledger();
+        let fee = crate::tokens::token_fee(ledger);
+        mint(ledger, user(1), 2_000_000);
+        let artwork_id = bounty_artwork(user(1), 1_000_000);
+        assert!(matches!(fund(ledger, user(1), artwork_id, 1_000_000), crate::bounty::BountyResult::Success(_)));
+        set_caller(user(2));
+        crate::add_critique(artwork_id, "Strong composition".to_string(), false);
+
+        set_caller(user(1));
+        fail_next_calls(1);
+        let payouts = block_on(award_bounty(artwork_id, vec![
+            CritiqueAward { critique_id: 0, share: AwardShare::Amount(400_000) },
+        ])).unwrap();
+        assert!(payouts[0].error.is_some());
+        assert!(block_on(crate::bounty::reconcile_pending_transfers(artwork_id)).unwrap().is_empty());
+        assert_eq!(balance_of(ledger, Account::from(user(2))), 400_000);
+
+        advance_time(31 * DAY_NS);
+        crate::bounty::with_bounty_mut(artwork_id, |b| b.expire_if_due());
+        block_on(refund_expired_bounty(BountyKey::Artwork(artwork_id), user(1), ledger));
+        assert_eq!(escrow(ledger, artwork_id), 0);
+        assert_eq!(status(artwork_id), BountyStatus::Refunded);
+        assert_eq!(balance_of(ledger, Account::from(user(1))), 1_000_000 - 2 * fee + 600_000 - 2 * fee);
+    }
+}
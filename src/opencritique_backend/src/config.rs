@@ -0,0 +1,147 @@
+use ic_cdk::query;
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+
+const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// Local replica ledger created by `dfx deploy icp_ledger_canister`
+const LOCAL_LEDGER: &str = "bkyz2-fmaaa-aaaaa-qaaaq-cai";
+const MAINNET_LEDGER: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+const DEFAULT_LEDGER_FEE: u64 = 10_000; // 0.0001 ICP in e8s
+/// Upper bound on the platform fee, in basis points
+pub(crate) const MAX_PLATFORM_FEE_BPS: u16 = 2_000;
+
+/// Where the canister runs
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, Default)]
+pub enum Environment {
+    Testing, // ledger calls go to the built-in mock ledger (builds with the `mock-ledger` feature only)
+    #[default]
+    Local,
+    Staging,
+    Mainnet,
+}
+
+/// Install/upgrade argument. Every field is optional: on install missing
+/// fields take their defaults, on upgrade they keep the stored value.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct InitArgs {
+    pub ledger_canister_id: Option<Principal>,
+    pub ledger_fee: Option<u64>,
+    pub environment: Option<Environment>,
+    pub admins: Option<Vec<Principal>>,
+    pub bounty_duration_ns: Option<u64>,
+    /// Funded bounties without deadline can be withdrawn after this long if nobody critiqued
+    pub unclaimed_withdraw_after_ns: Option<u64>,
+    /// Platform fee on bounty payouts and NFT sales, in basis points
+    pub platform_fee_bps: Option<u16>,
+}
+
+/// Deployment settings, kept in stable memory across upgrades
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CanisterConfig {
+    pub ledger_canister_id: Principal,
+    pub ledger_fee: u64,
+    pub environment: Environment,
+    /// Controllers are always admins in addition to these
+    pub admins: Vec<Principal>,
+    pub bounty_duration_ns: u64,
+    pub unclaimed_withdraw_after_ns: u64,
+    /// Platform fee in basis points (None for canisters configured before fees existed: no fee)
+    pub platform_fee_bps: Option<u16>,
+}
+
+impl Default for CanisterConfig {
+    fn default() -> Self {
+        Self {
+            ledger_canister_id: Principal::from_text(LOCAL_LEDGER).expect("Invalid ledger canister ID"),
+            ledger_fee: DEFAULT_LEDGER_FEE,
+            environment: Environment::Local,
+            admins: vec![],
+            bounty_duration_ns: 30 * DAY_NS,
+            unclaimed_withdraw_after_ns: 7 * DAY_NS,
+            platform_fee_bps: None,
+        }
+    }
+}
+
+impl CanisterConfig {
+    fn apply(&mut self, args: InitArgs) {
+        if let Some(environment) = args.environment {
+            self.environment = environment;
+        }
+        match args.ledger_canister_id {
+            Some(ledger) => self.ledger_canister_id = ledger,
+            // Switching to mainnet without naming a ledger means the real ICP ledger
+            None if args.environment == Some(Environment::Mainnet) => {
+                self.ledger_canister_id = Principal::from_text(MAINNET_LEDGER).expect("Invalid ledger canister ID");
+            }
+            None => {}
+        }
+        if let Some(fee) = args.ledger_fee {
+            self.ledger_fee = fee;
+        }
+        if let Some(admins) = args.admins {
+            self.admins = admins;
+        }
+        if let Some(duration) = args.bounty_duration_ns {
+            self.bounty_duration_ns = duration;
+        }
+        if let Some(delay) = args.unclaimed_withdraw_after_ns {
+            self.unclaimed_withdraw_after_ns = delay;
+        }
+        if let Some(bps) = args.platform_fee_bps {
+            self.platform_fee_bps = Some(bps.min(MAX_PLATFORM_FEE_BPS));
+        }
+    }
+}
+
+thread_local! {
+    static CONFIG: RefCell<CanisterConfig> = RefCell::new(CanisterConfig::default());
+}
+
+pub(crate) fn config() -> CanisterConfig {
+    CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Apply install/upgrade arguments on top of `base`
+pub(crate) fn load(base: CanisterConfig, args: Option<InitArgs>) {
+    let mut config = base;
+    if let Some(args) = args {
+        config.apply(args);
+    }
+    if config.environment == Environment::Testing && !cfg!(feature = "mock-ledger") {
+        ic_cdk::trap("The Testing environment needs a build with the mock-ledger feature");
+    }
+    CONFIG.with(|c| *c.borrow_mut() = config);
+}
+
+pub(crate) fn ledger_canister_id() -> Principal {
+    CONFIG.with(|c| c.borrow().ledger_canister_id)
+}
+
+pub(crate) fn ledger_fee() -> u64 {
+    CONFIG.with(|c| c.borrow().ledger_fee)
+}
+
+/// Ledger calls go to the mock ledger instead of the configured ledger canisters
+pub(crate) fn is_testing() -> bool {
+    cfg!(feature = "mock-ledger") && CONFIG.with(|c| c.borrow().environment == Environment::Testing)
+}
+
+pub(crate) fn platform_fee_bps() -> u16 {
+    CONFIG.with(|c| c.borrow().platform_fee_bps.unwrap_or(0))
+}
+
+pub(crate) fn set_platform_fee_bps(bps: u16) {
+    CONFIG.with(|c| c.borrow_mut().platform_fee_bps = Some(bps));
+}
+
+pub(crate) fn is_admin(principal: &Principal) -> bool {
+    CONFIG.with(|c| c.borrow().admins.contains(principal)) || crate::runtime::is_controller(principal)
+}
+
+#[query]
+pub fn get_canister_config() -> CanisterConfig {
+    config()
+}
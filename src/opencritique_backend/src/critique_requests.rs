@@ -0,0 +1,513 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use ic_ledger_types::Subaccount;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::bounty::{
+    block_note, escrow_icrc_account, with_bounty_mut, Account, AwardPayout, AwardShare, Bounty, BountyError,
+    BountyKey, BountyLock, BountyResult, BountyStatus, BountyView, TransferKind,
+};
+use crate::reputation::CriticTier;
+
+/// A brief asking for feedback on a set of related works. Artworks and
+/// versions are attached over time and share one escrow and one award decision.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CritiqueRequest {
+    pub id: u64,
+    pub author: Principal,
+    pub title: String,
+    pub brief: String, // what the artist wants feedback on
+    pub deadline: u64,
+    /// Only critiques by critics of at least this tier can be awarded
+    pub required_tier: CriticTier,
+    pub artwork_ids: Vec<u64>,
+    pub bounty: Bounty, // shared escrow, expires at the deadline
+    pub created_at: u64,
+}
+
+/// A critique request as returned to any caller, with critics of anonymous
+/// critiques hidden in its bounty
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CritiqueRequestView {
+    pub id: u64,
+    pub author: Principal,
+    pub title: String,
+    pub brief: String,
+    pub deadline: u64,
+    pub required_tier: CriticTier,
+    pub artwork_ids: Vec<u64>,
+    pub bounty: BountyView,
+    pub created_at: u64,
+}
+
+impl From<&CritiqueRequest> for CritiqueRequestView {
+    fn from(request: &CritiqueRequest) -> Self {
+        let hidden: HashSet<Principal> = crate::ARTWORKS.with(|arts| {
+            arts.borrow()
+                .iter()
+                .filter(|a| request.artwork_ids.contains(&a.id))
+                .flat_map(|a| a.anonymous_principals())
+                .collect()
+        });
+        Self {
+            id: request.id,
+            author: request.author,
+            title: request.title.clone(),
+            brief: request.brief.clone(),
+            deadline: request.deadline,
+            required_tier: request.required_tier,
+            artwork_ids: request.artwork_ids.clone(),
+            bounty: BountyView::redacted(&request.bounty, &hidden, false),
+            created_at: request.created_at,
+        }
+    }
+}
+
+/// Share of a critique request's bounty for one critique on any attached artwork
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RequestAward {
+    pub artwork_id: u64,
+    pub critique_id: u64,
+    pub share: AwardShare,
+}
+
+thread_local! {
+    static CRITIQUE_REQUESTS: RefCell<BTreeMap<u64, CritiqueRequest>> = const { RefCell::new(BTreeMap::new()) };
+    static REQUEST_ID: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Upgrade snapshot of critique requests and their escrows
+#[derive(CandidType, Deserialize)]
+pub(crate) struct CritiqueRequestState {
+    requests: BTreeMap<u64, CritiqueRequest>,
+    next_id: u64,
+}
+
+pub(crate) fn save_state() -> CritiqueRequestState {
+    CritiqueRequestState {
+        requests: CRITIQUE_REQUESTS.with(|s| s.take()),
+        next_id: REQUEST_ID.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: CritiqueRequestState) {
+    CRITIQUE_REQUESTS.with(|s| *s.borrow_mut() = state.requests);
+    REQUEST_ID.with(|s| *s.borrow_mut() = state.next_id);
+}
+
+pub(crate) fn with_request_bounty_mut<R>(request_id: u64, f: impl FnOnce(&mut Bounty) -> R) -> Option<R> {
+    CRITIQUE_REQUESTS.with(|r| r.borrow_mut().get_mut(&request_id).map(|request| f(&mut request.bounty)))
+}
+
+/// Ledger, recorded remaining amount and escrow subaccount of a request
+pub(crate) fn request_escrow(request_id: u64) -> Option<(Principal, u64, Subaccount)> {
+    CRITIQUE_REQUESTS.with(|r| {
+        r.borrow().get(&request_id).map(|request| {
+            let subaccount = request.bounty.subaccount
+                .unwrap_or_else(|| crate::bounty::request_subaccount(request.id, request.author));
+            (request.bounty.ledger, request.bounty.remaining(), subaccount)
+        })
+    })
+}
+
+/// Every request's bounty with its escrow subaccount
+pub(crate) fn request_bounties() -> Vec<(u64, Subaccount, Bounty)> {
+    CRITIQUE_REQUESTS.with(|r| {
+        r.borrow()
+            .values()
+            .map(|request| {
+                let subaccount = request.bounty.subaccount
+                    .unwrap_or_else(|| crate::bounty::request_subaccount(request.id, request.author));
+                (request.id, subaccount, request.bounty.clone())
+            })
+            .collect()
+    })
+}
+
+/// The artwork is attached to some critique request
+pub(crate) fn is_attached(artwork_id: u64) -> bool {
+    CRITIQUE_REQUESTS.with(|r| r.borrow().values().any(|req| req.artwork_ids.contains(&artwork_id)))
+}
+
+fn get_request(request_id: u64) -> Result<CritiqueRequest, BountyError> {
+    CRITIQUE_REQUESTS.with(|r| r.borrow().get(&request_id).cloned()).ok_or(BountyError::NotFound)
+}
+
+fn is_open(status: BountyStatus) -> bool {
+    matches!(status, BountyStatus::Prepared | BountyStatus::Funded | BountyStatus::PartiallyPaid)
+}
+
+/// Post a critique request. The bounty is funded afterwards like an artwork
+/// bounty, and expires at the deadline.
+#[update]
+pub fn create_critique_request(
+    title: String,
+    brief: String,
+    deadline: u64,
+    required_tier: CriticTier,
+    bounty_amount: u64,
+) -> Result<CritiqueRequestView, BountyError> {
+    let author = caller();
+    if brief.trim().is_empty() {
+        return Err(BountyError::InvalidTransition("a brief is required".to_string()));
+    }
+    if deadline <= time() {
+        return Err(BountyError::InvalidTransition("the deadline must be in the future".to_string()));
+    }
+    if bounty_amount == 0 {
+        return Err(BountyError::InvalidAmount);
+    }
+
+    let id = REQUEST_ID.with(|n| {
+        let mut n = n.borrow_mut();
+        *n += 1;
+        *n
+    });
+    let subaccount = crate::bounty::request_subaccount(id, author);
+    let request = CritiqueRequest {
+        id,
+        author,
+        title,
+        brief,
+        deadline,
+        required_tier,
+        artwork_ids: vec![],
+        bounty: Bounty {
+            subaccount: Some(subaccount),
+            intended_amount: bounty_amount,
+            expires_at: Some(deadline),
+            ..Bounty::default()
+        },
+        created_at: time(),
+    };
+    crate::escrow_audit::remember_escrow(BountyKey::Request(id), request.bounty.ledger, subaccount);
+    let view = CritiqueRequestView::from(&request);
+    CRITIQUE_REQUESTS.with(|r| r.borrow_mut().insert(id, request));
+    Ok(view)
+}
+
+/// Attach one of the caller's artworks to their open critique request. An
+/// artwork belongs to at most one request. Requests name their author, so
+/// anonymous artworks cannot be attached.
+#[update]
+pub fn attach_artwork_to_request(request_id: u64, artwork_id: u64) -> Result<CritiqueRequestView, BountyError> {
+    let author = caller();
+    let (owns_artwork, anonymous) = crate::ARTWORKS.with(|arts| {
+        arts.borrow()
+            .iter()
+            .find(|a| a.id == artwork_id && a.deleted_at.is_none())
+            .map(|a| (a.author == author, a.anonymous))
+    }).ok_or(BountyError::NotFound)?;
+    if !owns_artwork {
+        return Err(BountyError::NotAuthorized);
+    }
+    if anonymous {
+        return Err(BountyError::InvalidTransition("anonymous artworks cannot be attached to a request".to_string()));
+    }
+
+    CRITIQUE_REQUESTS.with(|r| {
+        let mut requests = r.borrow_mut();
+        if requests.values().any(|req| req.artwork_ids.contains(&artwork_id)) {
+            return Err(BountyError::InvalidTransition("artwork is already attached to a request".to_string()));
+        }
+        let request = requests.get_mut(&request_id).ok_or(BountyError::NotFound)?;
+        if request.author != author {
+            return Err(BountyError::NotAuthorized);
+        }
+        request.bounty.expire_if_due();
+        if !is_open(request.bounty.status) {
+            return Err(BountyError::Expired);
+        }
+        request.artwork_ids.push(artwork_id);
+        Ok(CritiqueRequestView::from(&*request))
+    })
+}
+
+/// Fund the caller's critique request in one step, after an `icrc2_approve`
+/// on the request's ledger with this canister as spender
+#[update]
+pub async fn fund_critique_request(request_id: u64, amount: u64) -> BountyResult {
+    let key = BountyKey::Request(request_id);
+    let _lock = match BountyLock::acquire(key) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(key, |bounty| bounty.expire_if_due());
+
+    let request = match get_request(request_id) {
+        Ok(request) => request,
+        Err(e) => return BountyResult::Error(e),
+    };
+    if request.author != caller() {
+        return BountyResult::Error(BountyError::NotAuthorized);
+    }
+    match request.bounty.status {
+        BountyStatus::Prepared => {}
+        BountyStatus::Expired => return BountyResult::Error(BountyError::Expired),
+        status => {
+            return BountyResult::Error(BountyError::InvalidTransition(format!("Bounty is already {:?}", status)));
+        }
+    }
+    if amount == 0 {
+        return BountyResult::Error(BountyError::InvalidAmount);
+    }
+
+    let ledger = request.bounty.ledger;
+    let subaccount = request.bounty.subaccount.unwrap_or_else(|| crate::bounty::request_subaccount(request_id, request.author));
+    let block_index = match crate::bounty::ledger_transfer_from(ledger, request_id.into(), request.author, subaccount, amount).await {
+        Ok(block_index) => block_index,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    let funded = with_bounty_mut(key, |bounty| {
+        if !bounty.has_deposit(block_index) {
+            bounty.actual_amount += amount;
+            bounty.deposits.push(crate::bounty::icrc2_deposit(request.author, amount, block_index));
+        }
+        if bounty.actual_amount >= bounty.intended_amount && bounty.status == BountyStatus::Prepared {
+            bounty.funded_at = Some(time());
+            let _ = bounty.transition(
+                BountyStatus::Funded,
+                Some(bounty.actual_amount),
+                Some(block_index),
+                "funded via icrc2_transfer_from",
+            );
+        }
+        bounty.status == BountyStatus::Funded
+    });
+
+    match funded {
+        Some(true) => BountyResult::Success(format!(
+            "Critique request {} funded with {}. {}",
+            request_id, crate::tokens::format_amount(ledger, amount), block_note(block_index)
+        )),
+        Some(false) => BountyResult::Success(format!(
+            "Received {} for critique request {}; it is not fully funded yet. {}",
+            crate::tokens::format_amount(ledger, amount), request_id, block_note(block_index)
+        )),
+        None => BountyResult::Error(BountyError::NotFound),
+    }
+}
+
+/// Confirm a direct deposit to the request's escrow account on the ledger
+#[update]
+pub async fn confirm_critique_request_funding(request_id: u64) -> BountyResult {
+    let key = BountyKey::Request(request_id);
+    let _lock = match BountyLock::acquire(key) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(key, |bounty| bounty.expire_if_due());
+
+    let request = match get_request(request_id) {
+        Ok(request) => request,
+        Err(e) => return BountyResult::Error(e),
+    };
+    match request.bounty.status {
+        BountyStatus::Prepared => {}
+        BountyStatus::Expired if request.bounty.funded_at.is_none() => {
+            return BountyResult::Error(BountyError::Expired);
+        }
+        status => {
+            return BountyResult::Success(format!("Critique request {} is already funded (status: {:?})", request_id, status));
+        }
+    }
+
+    let balance = match crate::bounty::escrow_balance(key).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => return BountyResult::Error(e),
+    };
+
+    let funded = with_bounty_mut(key, |bounty| {
+        bounty.actual_amount = balance;
+        if balance >= bounty.intended_amount && bounty.status == BountyStatus::Prepared {
+            bounty.funded_at = Some(time());
+            bounty.transition(BountyStatus::Funded, Some(balance), None, "escrow balance confirmed").is_ok()
+        } else {
+            false
+        }
+    });
+
+    match funded {
+        Some(true) => BountyResult::Success(format!(
+            "Critique request {} funded with {}",
+            request_id, crate::tokens::format_amount(request.bounty.ledger, balance)
+        )),
+        Some(false) => BountyResult::Error(BountyError::InsufficientFunds),
+        None => BountyResult::Error(BountyError::NotFound),
+    }
+}
+
+/// ICRC-1 escrow account of a critique request, for direct deposits
+#[query]
+pub fn get_critique_request_escrow_account(request_id: u64) -> Option<Account> {
+    request_escrow(request_id).map(|(_, _, subaccount)| escrow_icrc_account(subaccount))
+}
+
+/// Split the request's bounty across critiques on any of its artworks (author
+/// only). Critics below the request's tier cannot be awarded.
+#[update]
+pub async fn award_critique_request(request_id: u64, awards: Vec<RequestAward>) -> Result<Vec<AwardPayout>, BountyError> {
+    let key = BountyKey::Request(request_id);
+    let _lock = BountyLock::acquire(key)?;
+    with_bounty_mut(key, |bounty| bounty.expire_if_due());
+
+    let request = get_request(request_id)?;
+    if request.author != caller() {
+        return Err(BountyError::NotAuthorized);
+    }
+    match request.bounty.status {
+        BountyStatus::Prepared => return Err(BountyError::NotFunded),
+        BountyStatus::Expired => return Err(BountyError::Expired),
+        status if !status.is_payable() => return Err(BountyError::AlreadyReleased),
+        _ => {}
+    }
+    if awards.is_empty() {
+        return Err(BountyError::InvalidAmount);
+    }
+
+    // Resolve every award to a critic before moving any funds
+    let mut recipients: Vec<(u64, u64, Principal)> = Vec::new();
+    for award in &awards {
+        if !request.artwork_ids.contains(&award.artwork_id) {
+            return Err(BountyError::NotFound);
+        }
+        if recipients.iter().any(|(a, c, _)| *a == award.artwork_id && *c == award.critique_id) {
+            return Err(BountyError::InvalidAmount);
+        }
+        let (critic, rewarded) = crate::ARTWORKS.with(|arts| {
+            arts.borrow()
+                .iter()
+                .find(|a| a.id == award.artwork_id)
+                .and_then(|a| a.critiques.iter().find(|c| c.id == award.critique_id))
+                .map(|c| (c.critic, c.reward.is_some()))
+        }).ok_or(BountyError::NotFound)?;
+        let payout_pending = request.bounty.pending_transfers.iter().any(|t| {
+            matches!(t.kind, TransferKind::RequestPayout { artwork_id, critique_id }
+                if artwork_id == award.artwork_id && critique_id == award.critique_id)
+        });
+        if rewarded || payout_pending {
+            return Err(BountyError::AlreadyReleased);
+        }
+        if critic == request.author || crate::reputation::critic_tier(critic) < request.required_tier {
+            return Err(BountyError::NotAuthorized);
+        }
+        recipients.push((award.artwork_id, award.critique_id, critic));
+    }
+
+    let shares: Vec<AwardShare> = awards.into_iter().map(|a| a.share).collect();
+    let amounts = crate::bounty::award_amounts(&request.bounty, 0, &shares)?;
+
+    let mut results = Vec::new();
+    for ((artwork_id, critique_id, critic), amount) in recipients.into_iter().zip(amounts) {
+        let kind = TransferKind::RequestPayout { artwork_id, critique_id };
+        let result = crate::bounty::send_from_escrow(key, kind, critic, amount, "awarded critique").await;
+        results.push(match result {
+            Ok(block_index) => AwardPayout { critique_id, amount, block_index: Some(block_index), error: None },
+            Err(e) => AwardPayout { critique_id, amount, block_index: None, error: Some(format!("{:?}", e)) },
+        });
+    }
+    Ok(results)
+}
+
+/// Withdraw what is left of a request's bounty after its deadline or once it
+/// is fully paid (author only)
+#[update]
+pub async fn withdraw_critique_request(request_id: u64) -> BountyResult {
+    let key = BountyKey::Request(request_id);
+    let _lock = match BountyLock::acquire(key) {
+        Ok(lock) => lock,
+        Err(e) => return BountyResult::Error(e),
+    };
+    with_bounty_mut(key, |bounty| bounty.expire_if_due());
+
+    let request = match get_request(request_id) {
+        Ok(request) => request,
+        Err(e) => return BountyResult::Error(e),
+    };
+    if request.author != caller() {
+        return BountyResult::Error(BountyError::NotAuthorized);
+    }
+    let bounty = &request.bounty;
+    if bounty.status.is_settled() {
+        return BountyResult::Error(BountyError::AlreadyReleased);
+    }
+    if !bounty.pending_transfers.is_empty() {
+        return BountyResult::Error(BountyError::TransferPending(
+            "earlier transfers are unresolved; they are retried by the bounty sweep".to_string(),
+        ));
+    }
+    if !matches!(bounty.status, BountyStatus::Expired | BountyStatus::Paid) {
+        return BountyResult::Error(BountyError::NotReady);
+    }
+
+    let balance = match crate::bounty::escrow_balance(key).await {
+        Ok(balance) => balance.e8s,
+        Err(e) => return BountyResult::Error(e),
+    };
+    if balance <= bounty.fee() {
+        return BountyResult::Error(BountyError::InsufficientFunds);
+    }
+
+    match crate::bounty::refund_escrow(key, request.author, balance, "refunded to author").await {
+        Ok(outcome) => BountyResult::Success(outcome.message(bounty.ledger)),
+        Err(e) => BountyResult::Error(e),
+    }
+}
+
+/// Expire requests past their deadline and list those the bounty sweep should
+/// reconcile, and those it may refund: `(request_id, author, ledger)`
+pub(crate) fn due_request_jobs(auto_refund: bool) -> (Vec<u64>, Vec<(u64, Principal, Principal)>) {
+    let mut reconciliations = Vec::new();
+    let mut refunds = Vec::new();
+    CRITIQUE_REQUESTS.with(|r| {
+        for request in r.borrow_mut().values_mut() {
+            request.bounty.expire_if_due();
+            if !request.bounty.pending_transfers.is_empty() {
+                reconciliations.push(request.id);
+            } else if auto_refund && request.bounty.status == BountyStatus::Expired && request.bounty.funded_at.is_some() {
+                refunds.push((request.id, request.author, request.bounty.ledger));
+            }
+        }
+    });
+    (reconciliations, refunds)
+}
+
+#[query]
+pub fn get_critique_request(request_id: u64) -> Option<CritiqueRequestView> {
+    get_request(request_id).ok().as_ref().map(CritiqueRequestView::from)
+}
+
+/// Requests still open for critiques, newest first
+#[query]
+pub fn list_open_critique_requests() -> Vec<CritiqueRequestView> {
+    let now = time();
+    CRITIQUE_REQUESTS.with(|r| {
+        r.borrow()
+            .values()
+            .rev()
+            .filter(|req| is_open(req.bounty.status) && req.deadline > now)
+            .map(CritiqueRequestView::from)
+            .collect()
+    })
+}
+
+/// Critique requests posted by the caller, newest first
+#[query]
+pub fn get_my_critique_requests() -> Vec<CritiqueRequestView> {
+    let author = caller();
+    CRITIQUE_REQUESTS.with(|r| {
+        r.borrow().values().rev().filter(|req| req.author == author).map(CritiqueRequestView::from).collect()
+    })
+}
+
+/// The critique request an artwork is attached to, if any
+#[query]
+pub fn get_artwork_critique_request(artwork_id: u64) -> Option<CritiqueRequestView> {
+    CRITIQUE_REQUESTS.with(|r| {
+        r.borrow().values().find(|req| req.artwork_ids.contains(&artwork_id)).map(CritiqueRequestView::from)
+    })
+}
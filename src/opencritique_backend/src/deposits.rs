@@ -0,0 +1,182 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+use ic_ledger_types::{query_archived_blocks, query_blocks, AccountIdentifier, Block, GetBlocksArgs, Operation};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::bounty::{with_bounty_mut, BountyDeposit, BountyKey};
+use crate::config::{is_testing, ledger_canister_id};
+
+// Upper bound on blocks read per scan, to stay within one message's limits
+const MAX_BLOCKS_PER_SCAN: u64 = 2_000;
+
+/// Outcome of one pass over the ICP ledger
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DepositScanReport {
+    pub scanned_at: u64,
+    pub from_block: u64,
+    pub next_block: u64, // where the next scan starts
+    pub chain_length: u64,
+    pub deposits_found: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct DepositScanStatus {
+    pub next_block: Option<u64>, // None until the first scan, which starts at the chain tip
+    pub last_scan: Option<DepositScanReport>,
+}
+
+thread_local! {
+    static NEXT_BLOCK: RefCell<Option<u64>> = const { RefCell::new(None) };
+    static LAST_SCAN: RefCell<Option<DepositScanReport>> = const { RefCell::new(None) };
+    static SCANNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+/// Upgrade snapshot of the deposit scan position
+#[derive(CandidType, Deserialize)]
+pub(crate) struct DepositScanState {
+    next_block: Option<u64>,
+    last_scan: Option<DepositScanReport>,
+}
+
+pub(crate) fn save_state() -> DepositScanState {
+    DepositScanState {
+        next_block: NEXT_BLOCK.with(|s| s.take()),
+        last_scan: LAST_SCAN.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: DepositScanState) {
+    NEXT_BLOCK.with(|s| *s.borrow_mut() = state.next_block);
+    LAST_SCAN.with(|s| *s.borrow_mut() = state.last_scan);
+}
+
+// Clears the running flag when the scan ends, including on trap
+struct ScanGuard;
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        SCANNING.with(|s| *s.borrow_mut() = false);
+    }
+}
+
+fn call_error((code, msg): (ic_cdk::api::call::RejectionCode, String)) -> String {
+    format!("query_blocks failed: {}: {}", code as u8, msg)
+}
+
+/// Read new ICP ledger blocks and attribute transfers into escrow accounts to
+/// their bounty, with sender and block index. Deposits count toward funding,
+/// so bounties paid to their hex address become funded without a confirm call.
+/// Bounties in other tokens are funded through icrc2 and are not scanned.
+pub(crate) async fn scan_deposits() -> Result<DepositScanReport, String> {
+    if is_testing() {
+        return Err("The mock ledger keeps no block history to scan".to_string());
+    }
+    if SCANNING.with(|s| s.replace(true)) {
+        return Err("A deposit scan is already running".to_string());
+    }
+    let _guard = ScanGuard;
+
+    let ledger = ledger_canister_id();
+    let escrows: BTreeMap<AccountIdentifier, BountyKey> = crate::escrow_audit::live_escrows()
+        .into_iter()
+        .filter(|(_, _, bounty)| bounty.ledger == ledger)
+        .map(|(key, subaccount, _)| (AccountIdentifier::new(&crate::runtime::id(), &subaccount), key))
+        .collect();
+
+    let tip = query_blocks(ledger, GetBlocksArgs { start: 0, length: 0 })
+        .await
+        .map_err(call_error)?
+        .chain_length;
+    let from_block = NEXT_BLOCK.with(|n| *n.borrow()).unwrap_or(tip);
+    let end = tip.min(from_block + MAX_BLOCKS_PER_SCAN);
+
+    let mut blocks: Vec<(u64, Block)> = Vec::new();
+    if end > from_block {
+        let response = query_blocks(ledger, GetBlocksArgs { start: from_block, length: end - from_block })
+            .await
+            .map_err(call_error)?;
+        // Older blocks may have moved to archive canisters
+        for range in response.archived_blocks {
+            let args = GetBlocksArgs { start: range.start, length: range.length };
+            match query_archived_blocks(&range.callback, args).await.map_err(call_error)? {
+                Ok(archived) => blocks.extend((range.start..).zip(archived.blocks)),
+                Err(e) => return Err(format!("Archive returned {:?}", e)),
+            }
+        }
+        blocks.extend((response.first_block_index..).zip(response.blocks));
+    }
+    blocks.sort_by_key(|(index, _)| *index);
+
+    // Only advance over a gapless run, so blocks a reply left out are read next time
+    let mut next_block = from_block;
+    let mut deposits_found = 0;
+    for (index, block) in blocks {
+        if index < next_block {
+            continue;
+        }
+        if index > next_block {
+            break;
+        }
+        next_block += 1;
+
+        let (from, to, amount) = match block.transaction.operation {
+            Some(Operation::Transfer { from, to, amount, .. })
+            | Some(Operation::TransferFrom { from, to, amount, .. }) => (Some(from), to, amount),
+            Some(Operation::Mint { to, amount }) => (None, to, amount),
+            _ => continue,
+        };
+        let Some(key) = escrows.get(&to) else { continue };
+
+        let deposit = BountyDeposit {
+            block_index: index,
+            from: from.map(|f| f.to_hex()),
+            amount: amount.e8s(),
+            timestamp: block.timestamp.timestamp_nanos,
+        };
+        if with_bounty_mut(*key, |bounty| bounty.record_deposit(deposit)) == Some(true) {
+            deposits_found += 1;
+        }
+    }
+
+    NEXT_BLOCK.with(|n| *n.borrow_mut() = Some(next_block));
+    let report = DepositScanReport {
+        scanned_at: time(),
+        from_block,
+        next_block,
+        chain_length: tip,
+        deposits_found,
+    };
+    LAST_SCAN.with(|s| *s.borrow_mut() = Some(report.clone()));
+    Ok(report)
+}
+
+/// Scan the ledger for escrow deposits now instead of waiting for the bounty sweep (admin only)
+#[update]
+pub async fn scan_escrow_deposits() -> Result<DepositScanReport, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    scan_deposits().await
+}
+
+/// Move the scan position, e.g. back to a block before deposits that were
+/// missed (admin only). Deposits already recorded are not counted twice.
+#[update]
+pub fn set_deposit_scan_position(next_block: u64) -> Result<String, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    NEXT_BLOCK.with(|n| *n.borrow_mut() = Some(next_block));
+    Ok(format!("The next deposit scan starts at block {}", next_block))
+}
+
+#[query]
+pub fn get_deposit_scan_status() -> DepositScanStatus {
+    DepositScanStatus {
+        next_block: NEXT_BLOCK.with(|n| *n.borrow()),
+        last_scan: LAST_SCAN.with(|s| s.borrow().clone()),
+    }
+}
@@ -0,0 +1,403 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::bounty::{with_bounty_mut, BountyError, BountyStatus};
+
+const ARBITER_COUNT: usize = 3;
+/// Lifetime reputation needed to be drawn as an arbiter
+const MIN_ARBITER_POINTS: u64 = 50;
+const VOTING_PERIOD_NS: u64 = 3 * 24 * 60 * 60 * 1_000_000_000;
+/// The bounty sweep stops retrying an upheld dispute's payout after this many failures
+const MAX_PAYOUT_ATTEMPTS: u32 = 5;
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum DisputeStatus {
+    Open,
+    Upheld,    // ruled for the critic, who is paid the requested amount
+    Dismissed, // ruled for the artist
+    Closed,    // upheld, but closed unpaid by an admin because the payout could not be made
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DisputeVote {
+    pub arbiter: Principal,
+    pub uphold: bool,
+    pub comment: String,
+    pub timestamp: u64,
+}
+
+/// A critic's complaint that the artist refused to pay for a critique.
+/// Kept on the bounty after the ruling as its dispute history.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BountyDispute {
+    pub id: u64,
+    pub critique_id: u64,
+    pub opened_by: Principal,
+    pub amount: u64, // requested payout
+    pub reason: String,
+    pub opened_at: u64,
+    pub voting_ends_at: u64,
+    pub arbiters: Vec<Principal>,
+    pub votes: Vec<DisputeVote>,
+    pub status: DisputeStatus,
+    pub ruled_at: Option<u64>,
+    pub paid_at: Option<u64>,
+    pub payout_block: Option<u64>,
+    pub payout_error: Option<String>,
+    #[serde(default)]
+    pub payout_attempts: u32,
+    #[serde(default)]
+    pub close_reason: Option<String>,
+}
+
+impl BountyDispute {
+    /// Upheld, or still open, and not paid yet: the amount may still leave the escrow
+    pub fn is_unresolved(&self) -> bool {
+        match self.status {
+            DisputeStatus::Open => true,
+            DisputeStatus::Upheld => self.paid_at.is_none(),
+            DisputeStatus::Dismissed | DisputeStatus::Closed => false,
+        }
+    }
+
+    /// Copy that does not reveal the principals in `hidden`, e.g. the critic
+    /// behind an anonymous critique
+    pub(crate) fn redacted(&self, hidden: &HashSet<Principal>) -> BountyDispute {
+        let mut dispute = self.clone();
+        if hidden.contains(&dispute.opened_by) {
+            dispute.opened_by = Principal::anonymous();
+        }
+        dispute
+    }
+
+    // Side with a strict majority of all arbiters, if any
+    fn majority(&self) -> Option<bool> {
+        let upheld = self.votes.iter().filter(|v| v.uphold).count();
+        let dismissed = self.votes.len() - upheld;
+        let needed = self.arbiters.len() / 2 + 1;
+        if upheld >= needed {
+            Some(true)
+        } else if dismissed >= needed {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+// Draw arbiters: high-reputation users with no stake in the bounty, in an
+// order set by the subnet's randomness. Falls back to the configured admins
+// when there are not enough of them.
+async fn select_arbiters(author: Principal, critic: Principal) -> Result<Vec<Principal>, BountyError> {
+    let mut candidates: Vec<Principal> = crate::reputation::users_with_points(MIN_ARBITER_POINTS)
+        .into_iter()
+        .filter(|p| *p != author && *p != critic)
+        .collect();
+
+    if candidates.len() >= ARBITER_COUNT {
+        let (seed,) = ic_cdk::api::management_canister::main::raw_rand()
+            .await
+            .map_err(|(code, msg)| BountyError::TransferFailed(format!("raw_rand failed: {}: {}", code as u8, msg)))?;
+        candidates.sort_by_cached_key(|p| {
+            let mut hasher = Sha256::new();
+            hasher.update(&seed);
+            hasher.update(p.as_slice());
+            hasher.finalize()
+        });
+        candidates.truncate(ARBITER_COUNT);
+        return Ok(candidates);
+    }
+
+    let admins: Vec<Principal> = crate::config::config()
+        .admins
+        .into_iter()
+        .filter(|p| *p != author && *p != critic)
+        .collect();
+    if admins.is_empty() {
+        return Err(BountyError::NotReady);
+    }
+    Ok(admins)
+}
+
+/// Open a dispute over one of the caller's critiques on a funded bounty the
+/// artist did not pay out. Critiques that were paid or have a claim that was
+/// paid or is still undecided cannot be disputed. The escrow cannot be
+/// withdrawn while the dispute is open.
+#[update]
+pub async fn open_bounty_dispute(artwork_id: u64, critique_id: u64, amount: u64, reason: String) -> Result<BountyDispute, BountyError> {
+    let critic = caller();
+    if reason.trim().is_empty() {
+        return Err(BountyError::InvalidTransition("a reason is required".to_string()));
+    }
+
+    let author = crate::ARTWORKS.with(|arts| {
+        let arts = arts.borrow();
+        let artwork = arts.iter().find(|a| a.id == artwork_id).ok_or(BountyError::NotFound)?;
+        let critique = artwork.critiques
+            .iter()
+            .find(|c| c.id == critique_id)
+            .ok_or(BountyError::NotFound)?;
+        if critique.critic != critic || critic == artwork.author {
+            return Err(BountyError::NotAuthorized);
+        }
+        if critique.reward.is_some() {
+            return Err(BountyError::AlreadyReleased);
+        }
+        Ok(artwork.author)
+    })?;
+
+    let arbiters = select_arbiters(author, critic).await?;
+
+    let now = time();
+    let dispute = with_bounty_mut(artwork_id, |bounty| {
+        bounty.expire_if_due();
+        let funded = bounty.status.is_payable() || (bounty.status == BountyStatus::Expired && bounty.funded_at.is_some());
+        if !funded {
+            return Err(BountyError::NotFunded);
+        }
+        // A ruling is final: each critique can be disputed once
+        if bounty.disputes.iter().any(|d| d.critique_id == critique_id) {
+            return Err(BountyError::AlreadyReleased);
+        }
+        if bounty.has_pending_payout(critique_id) || crate::bounty_claims::is_claimed(artwork_id, critique_id) {
+            return Err(BountyError::AlreadyReleased);
+        }
+        if amount == 0 {
+            return Err(BountyError::InvalidAmount);
+        }
+        bounty.check_payable(amount, crate::bounty::reserved_for_critics(artwork_id, bounty, None))?;
+
+        let dispute = BountyDispute {
+            id: bounty.disputes.len() as u64 + 1,
+            critique_id,
+            opened_by: critic,
+            amount,
+            reason,
+            opened_at: now,
+            voting_ends_at: now + VOTING_PERIOD_NS,
+            arbiters,
+            votes: vec![],
+            status: DisputeStatus::Open,
+            ruled_at: None,
+            paid_at: None,
+            payout_block: None,
+            payout_error: None,
+            payout_attempts: 0,
+            close_reason: None,
+        };
+        bounty.disputes.push(dispute.clone());
+        Ok(dispute)
+    }).ok_or(BountyError::NotFound)??;
+
+    crate::notifications::notify(author, Some(artwork_id), format!(
+        "A critic opened dispute #{} over your bounty. The escrow is held until arbiters rule on it.",
+        dispute.id
+    ));
+    for arbiter in &dispute.arbiters {
+        crate::notifications::notify(*arbiter, Some(artwork_id), format!(
+            "You were selected to arbitrate dispute #{} on artwork {}. Please vote before the deadline.",
+            dispute.id, artwork_id
+        ));
+    }
+    Ok(dispute)
+}
+
+/// Cast the caller's vote as an assigned arbiter. The dispute is ruled as soon
+/// as a majority of arbiters agrees.
+#[update]
+pub async fn vote_on_dispute(artwork_id: u64, dispute_id: u64, uphold: bool, comment: String) -> Result<BountyDispute, BountyError> {
+    let arbiter = caller();
+    let decided = with_bounty_mut(artwork_id, |bounty| {
+        let dispute = bounty.disputes
+            .iter_mut()
+            .find(|d| d.id == dispute_id)
+            .ok_or(BountyError::NotFound)?;
+        if dispute.status != DisputeStatus::Open {
+            return Err(BountyError::InvalidTransition(format!("dispute is {:?}", dispute.status)));
+        }
+        if !dispute.arbiters.contains(&arbiter) {
+            return Err(BountyError::NotAuthorized);
+        }
+        if dispute.votes.iter().any(|v| v.arbiter == arbiter) {
+            return Err(BountyError::AlreadyReleased);
+        }
+        dispute.votes.push(DisputeVote { arbiter, uphold, comment, timestamp: time() });
+        Ok(dispute.majority())
+    }).ok_or(BountyError::NotFound)??;
+
+    if let Some(upheld) = decided {
+        rule(artwork_id, dispute_id, upheld).await;
+    }
+    find_dispute(artwork_id, dispute_id).ok_or(BountyError::NotFound)
+}
+
+fn find_dispute(artwork_id: u64, dispute_id: u64) -> Option<BountyDispute> {
+    crate::bounty::artwork_bounty(artwork_id)
+        .and_then(|b| b.disputes.into_iter().find(|d| d.id == dispute_id))
+}
+
+// Apply a ruling and, if upheld, release the requested amount to the critic
+async fn rule(artwork_id: u64, dispute_id: u64, upheld: bool) {
+    let ruled = with_bounty_mut(artwork_id, |bounty| {
+        let dispute = bounty.disputes.iter_mut().find(|d| d.id == dispute_id)?;
+        let newly_ruled = dispute.status == DisputeStatus::Open;
+        if newly_ruled {
+            dispute.status = if upheld { DisputeStatus::Upheld } else { DisputeStatus::Dismissed };
+            dispute.ruled_at = Some(time());
+        }
+        Some((dispute.clone(), newly_ruled))
+    }).flatten();
+    let Some((dispute, newly_ruled)) = ruled else { return };
+
+    if dispute.status == DisputeStatus::Upheld && dispute.paid_at.is_none() {
+        let result = crate::bounty::pay_critique(artwork_id, dispute.critique_id, dispute.amount).await;
+        with_bounty_mut(artwork_id, |bounty| {
+            if let Some(d) = bounty.disputes.iter_mut().find(|d| d.id == dispute_id) {
+                match result {
+                    Ok(payout) => {
+                        d.paid_at = Some(time());
+                        d.payout_block = payout.block_index;
+                        d.payout_error = None;
+                    }
+                    // Retried by the bounty sweep, up to MAX_PAYOUT_ATTEMPTS times
+                    Err(e) => {
+                        d.payout_error = Some(format!("{:?}", e));
+                        d.payout_attempts += 1;
+                    }
+                }
+            }
+        });
+    }
+
+    if !newly_ruled {
+        return;
+    }
+    let outcome = match dispute.status {
+        DisputeStatus::Upheld => "upheld: the critic is paid from the escrow",
+        _ => "dismissed",
+    };
+    let message = format!("Dispute #{} on artwork {} was {}.", dispute_id, artwork_id, outcome);
+    crate::notifications::notify(dispute.opened_by, Some(artwork_id), message.clone());
+    if let Some(author) = crate::ARTWORKS.with(|arts| arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.author)) {
+        crate::notifications::notify(author, Some(artwork_id), message);
+    }
+}
+
+/// Rule disputes whose voting period is over by the votes cast (a tie or no
+/// votes dismisses the dispute) and retry failed payouts of upheld ones, until
+/// they have failed MAX_PAYOUT_ATTEMPTS times. Run by the bounty sweep.
+pub(crate) async fn settle_disputes(now: u64) {
+    let mut due: Vec<(u64, u64, bool)> = Vec::new();
+    crate::ARTWORKS.with(|arts| {
+        for art in arts.borrow().iter() {
+            let Some(bounty) = art.bounty.as_ref() else { continue };
+            for d in &bounty.disputes {
+                match d.status {
+                    DisputeStatus::Open if now > d.voting_ends_at => {
+                        let upheld = d.votes.iter().filter(|v| v.uphold).count();
+                        due.push((art.id, d.id, upheld * 2 > d.votes.len()));
+                    }
+                    DisputeStatus::Upheld if d.paid_at.is_none() && d.payout_attempts < MAX_PAYOUT_ATTEMPTS => {
+                        due.push((art.id, d.id, true));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    for (artwork_id, dispute_id, upheld) in due {
+        rule(artwork_id, dispute_id, upheld).await;
+    }
+}
+
+/// Retry the payout of an upheld dispute the bounty sweep gave up on (admin only)
+#[update]
+pub async fn retry_dispute_payout(artwork_id: u64, dispute_id: u64) -> Result<BountyDispute, BountyError> {
+    if !crate::is_admin(&caller()) {
+        return Err(BountyError::NotAuthorized);
+    }
+    let dispute = find_dispute(artwork_id, dispute_id).ok_or(BountyError::NotFound)?;
+    if dispute.status != DisputeStatus::Upheld || dispute.paid_at.is_some() {
+        return Err(BountyError::InvalidTransition(format!("dispute is {:?}", dispute.status)));
+    }
+    rule(artwork_id, dispute_id, true).await;
+    find_dispute(artwork_id, dispute_id).ok_or(BountyError::NotFound)
+}
+
+/// Close an upheld dispute whose payout cannot be made, e.g. because the
+/// critique was paid another way or the escrow no longer holds the amount
+/// (admin only). The critic is not paid and the escrow is released.
+#[update]
+pub fn close_unpaid_dispute(artwork_id: u64, dispute_id: u64, reason: String) -> Result<BountyDispute, BountyError> {
+    if !crate::is_admin(&caller()) {
+        return Err(BountyError::NotAuthorized);
+    }
+    if reason.trim().is_empty() {
+        return Err(BountyError::InvalidTransition("a reason is required".to_string()));
+    }
+    // A payout in progress holds the lock
+    let _lock = crate::bounty::BountyLock::acquire(artwork_id)?;
+
+    let dispute = with_bounty_mut(artwork_id, |bounty| {
+        let payout_pending = bounty.disputes
+            .iter()
+            .find(|d| d.id == dispute_id)
+            .is_some_and(|d| bounty.has_pending_payout(d.critique_id));
+        let dispute = bounty.disputes
+            .iter_mut()
+            .find(|d| d.id == dispute_id)
+            .ok_or(BountyError::NotFound)?;
+        if dispute.status != DisputeStatus::Upheld || dispute.paid_at.is_some() {
+            return Err(BountyError::InvalidTransition(format!("dispute is {:?}", dispute.status)));
+        }
+        // The payout may still go through; reconcile it first
+        if payout_pending {
+            return Err(BountyError::TransferPending("the dispute payout is still pending".to_string()));
+        }
+        dispute.status = DisputeStatus::Closed;
+        dispute.close_reason = Some(reason);
+        Ok(dispute.clone())
+    }).ok_or(BountyError::NotFound)??;
+
+    let message = format!(
+        "Dispute #{} on artwork {} was closed without payout: {}",
+        dispute_id, artwork_id, dispute.close_reason.clone().unwrap_or_default()
+    );
+    crate::notifications::notify(dispute.opened_by, Some(artwork_id), message.clone());
+    if let Some(author) = crate::ARTWORKS.with(|arts| arts.borrow().iter().find(|a| a.id == artwork_id).map(|a| a.author)) {
+        crate::notifications::notify(author, Some(artwork_id), message);
+    }
+    Ok(dispute)
+}
+
+/// Dispute history of an artwork's bounty
+#[query]
+pub fn get_bounty_disputes(artwork_id: u64) -> Vec<BountyDispute> {
+    crate::bounty::get_artwork_bounty(artwork_id)
+        .map(|b| b.disputes)
+        .unwrap_or_default()
+}
+
+/// Open disputes the caller was assigned to and has not voted on yet
+#[query]
+pub fn get_my_arbitration_assignments() -> Vec<(u64, BountyDispute)> {
+    let arbiter = caller();
+    crate::ARTWORKS.with(|arts| {
+        arts.borrow()
+            .iter()
+            .filter_map(|a| a.bounty.as_ref().map(|b| (a, b)))
+            .flat_map(|(a, b)| b.disputes.iter().map(move |d| (a, d)))
+            .filter(|(_, d)| {
+                d.status == DisputeStatus::Open
+                    && d.arbiters.contains(&arbiter)
+                    && !d.votes.iter().any(|v| v.arbiter == arbiter)
+            })
+            .map(|(a, d)| (a.id, d.redacted(&a.anonymous_principals())))
+            .collect()
+    })
+}
@@ -0,0 +1,265 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use ic_ledger_types::Subaccount;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::bounty::{escrow_icrc_account, Account, Bounty, BountyKey, BountyStatus};
+
+/// An escrow subaccount the canister handed out, on one ledger. Entries
+/// outlive the bounty that used them, so funds left behind by deleted artworks,
+/// or on the ledger a bounty used before changing its token, can be found.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+struct KnownEscrow {
+    owner: BountyKey,
+    registered_at: u64,
+}
+
+/// How an escrow's ledger balance compares to the bounty records
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum AuditFinding {
+    Balanced,
+    Surplus,     // more on the ledger than recorded, e.g. unconfirmed deposits
+    Shortfall,   // less on the ledger than recorded
+    Orphaned,    // holds funds but no bounty uses it any more
+    Unreachable, // the balance could not be read
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct EscrowAuditEntry {
+    pub owner: BountyKey,
+    pub ledger: Principal,
+    pub account: Account,
+    pub status: Option<BountyStatus>, // None for orphaned escrows
+    pub funded: u64,
+    pub paid: u64,
+    pub refunded: u64,
+    pub fees: u64,
+    pub pending: u64, // transfers whose outcome is still unknown
+    pub expected: u64, // funded - paid - refunded - fees - pending
+    pub on_ledger: Option<u64>,
+    pub finding: AuditFinding,
+    pub error: Option<String>,
+}
+
+/// Sums for one ledger over all audited escrows
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct LedgerTotals {
+    pub ledger: Principal,
+    pub expected: u64,
+    pub on_ledger: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct EscrowAuditReport {
+    pub generated_at: u64,
+    pub entries: Vec<EscrowAuditEntry>,
+    pub totals: Vec<LedgerTotals>,
+    pub mismatches: u64,
+    pub orphans: u64,
+}
+
+thread_local! {
+    // (subaccount, ledger) -> first bounty that used it
+    static KNOWN_ESCROWS: RefCell<BTreeMap<([u8; 32], Principal), KnownEscrow>> = const { RefCell::new(BTreeMap::new()) };
+    static LAST_AUDIT: RefCell<Option<EscrowAuditReport>> = const { RefCell::new(None) };
+}
+
+/// Upgrade snapshot of known escrows and the last audit
+#[derive(CandidType, Deserialize)]
+pub(crate) struct EscrowAuditState {
+    known_escrows: BTreeMap<([u8; 32], Principal), KnownEscrow>,
+    last_audit: Option<EscrowAuditReport>,
+}
+
+pub(crate) fn save_state() -> EscrowAuditState {
+    EscrowAuditState {
+        known_escrows: KNOWN_ESCROWS.with(|s| s.take()),
+        last_audit: LAST_AUDIT.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: EscrowAuditState) {
+    KNOWN_ESCROWS.with(|s| *s.borrow_mut() = state.known_escrows);
+    LAST_AUDIT.with(|s| *s.borrow_mut() = state.last_audit);
+}
+
+/// Record an escrow subaccount when a bounty starts using it on `ledger`
+pub(crate) fn remember_escrow(owner: BountyKey, ledger: Principal, subaccount: Subaccount) {
+    KNOWN_ESCROWS.with(|k| {
+        k.borrow_mut().entry((subaccount.0, ledger)).or_insert(KnownEscrow { owner, registered_at: time() });
+    });
+}
+
+/// Every bounty currently in use, with its escrow subaccount
+pub(crate) fn live_escrows() -> Vec<(BountyKey, Subaccount, Bounty)> {
+    let mut escrows: Vec<(BountyKey, Subaccount, Bounty)> = crate::ARTWORKS.with(|arts| {
+        arts.borrow()
+            .iter()
+            .filter_map(|a| {
+                let bounty = a.bounty.as_ref()?;
+                let subaccount = crate::bounty::stored_subaccount(a.id, a.author, bounty);
+                Some((BountyKey::Artwork(a.id), subaccount, bounty.clone()))
+            })
+            .collect()
+    });
+    escrows.extend(
+        crate::critique_requests::request_bounties()
+            .into_iter()
+            .map(|(request_id, subaccount, bounty)| (BountyKey::Request(request_id), subaccount, bounty)),
+    );
+    escrows
+}
+
+async fn read_balance(ledger: Principal, account: Account) -> Result<u64, String> {
+    crate::bounty::ledger_balance(ledger, account).await.map_err(|e| format!("{:?}", e))
+}
+
+/// Compare every escrow subaccount's ledger balance with the bounty records
+/// and flag mismatches and orphaned escrows (admin only). The report is kept
+/// for `get_last_escrow_audit` and `export_escrow_audit_csv`.
+#[update]
+pub async fn run_escrow_audit() -> Result<EscrowAuditReport, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+
+    let live = live_escrows();
+    for (owner, subaccount, bounty) in &live {
+        remember_escrow(*owner, bounty.ledger, *subaccount);
+    }
+
+    let mut entries = Vec::new();
+    for (owner, subaccount, bounty) in &live {
+        let account = escrow_icrc_account(*subaccount);
+        let paid = bounty.paid_amount + bounty.platform_fees; // to critics and the treasury
+        let fees = bounty.fees_paid;
+        let pending: u64 = bounty.pending_transfers.iter().map(|t| t.amount + t.fee).sum();
+        let refunded: u64 = bounty.transitions
+            .iter()
+            .filter(|t| t.to == BountyStatus::Refunded)
+            .filter_map(|t| t.amount)
+            .sum::<u64>()
+            + bounty.contributions.iter().map(|c| c.refunded).sum::<u64>();
+        let expected = bounty.actual_amount.saturating_sub(paid + fees + pending + refunded);
+
+        let (on_ledger, finding, error) = match read_balance(bounty.ledger, account).await {
+            Ok(balance) if balance == expected => (Some(balance), AuditFinding::Balanced, None),
+            Ok(balance) if balance > expected => (Some(balance), AuditFinding::Surplus, None),
+            Ok(balance) => (Some(balance), AuditFinding::Shortfall, None),
+            Err(e) => (None, AuditFinding::Unreachable, Some(e)),
+        };
+        entries.push(EscrowAuditEntry {
+            owner: *owner,
+            ledger: bounty.ledger,
+            account,
+            status: Some(bounty.status),
+            funded: bounty.actual_amount,
+            paid,
+            refunded,
+            fees,
+            pending,
+            expected,
+            on_ledger,
+            finding,
+            error,
+        });
+    }
+
+    // Known subaccounts no bounty uses any more on that ledger; only those holding funds are reported
+    let orphans: Vec<(([u8; 32], Principal), KnownEscrow)> = KNOWN_ESCROWS.with(|k| {
+        k.borrow()
+            .iter()
+            .filter(|((subaccount, ledger), _)| {
+                !live.iter().any(|(_, s, bounty)| s.0 == *subaccount && bounty.ledger == *ledger)
+            })
+            .map(|(key, known)| (*key, known.clone()))
+            .collect()
+    });
+    for ((subaccount, ledger), known) in orphans {
+        let account = escrow_icrc_account(Subaccount(subaccount));
+        let (on_ledger, finding, error) = match read_balance(ledger, account).await {
+            Ok(0) => continue,
+            Ok(balance) => (Some(balance), AuditFinding::Orphaned, None),
+            Err(e) => (None, AuditFinding::Unreachable, Some(e)),
+        };
+        entries.push(EscrowAuditEntry {
+            owner: known.owner,
+            ledger,
+            account,
+            status: None,
+            funded: 0,
+            paid: 0,
+            refunded: 0,
+            fees: 0,
+            pending: 0,
+            expected: 0,
+            on_ledger,
+            finding,
+            error,
+        });
+    }
+
+    let mut totals: BTreeMap<Principal, LedgerTotals> = BTreeMap::new();
+    for entry in &entries {
+        let t = totals.entry(entry.ledger).or_insert(LedgerTotals { ledger: entry.ledger, expected: 0, on_ledger: 0 });
+        t.expected += entry.expected;
+        t.on_ledger += entry.on_ledger.unwrap_or(0);
+    }
+
+    let report = EscrowAuditReport {
+        generated_at: time(),
+        mismatches: entries.iter().filter(|e| e.finding != AuditFinding::Balanced).count() as u64,
+        orphans: entries.iter().filter(|e| e.status.is_none()).count() as u64,
+        totals: totals.into_values().collect(),
+        entries,
+    };
+    LAST_AUDIT.with(|a| *a.borrow_mut() = Some(report.clone()));
+    Ok(report)
+}
+
+/// Result of the latest escrow audit (admin only)
+#[query]
+pub fn get_last_escrow_audit() -> Result<Option<EscrowAuditReport>, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    Ok(LAST_AUDIT.with(|a| a.borrow().clone()))
+}
+
+/// The latest escrow audit as CSV, one line per escrow (admin only)
+#[query]
+pub fn export_escrow_audit_csv() -> Result<String, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    let report = LAST_AUDIT.with(|a| a.borrow().clone()).ok_or("No audit has been run yet")?;
+
+    let mut csv = String::from("owner,ledger,subaccount,status,funded,paid,refunded,fees,pending,expected,on_ledger,finding,error\n");
+    for e in &report.entries {
+        let owner = match e.owner {
+            BountyKey::Artwork(id) => format!("artwork:{}", id),
+            BountyKey::Request(id) => format!("request:{}", id),
+        };
+        let subaccount = e.account.subaccount.map(hex::encode).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{:?},{}\n",
+            owner,
+            e.ledger.to_text(),
+            subaccount,
+            e.status.map(|s| format!("{:?}", s)).unwrap_or_default(),
+            e.funded,
+            e.paid,
+            e.refunded,
+            e.fees,
+            e.pending,
+            e.expected,
+            e.on_ledger.map(|b| b.to_string()).unwrap_or_default(),
+            e.finding,
+            e.error.clone().unwrap_or_default().replace(',', ";"),
+        ));
+    }
+    Ok(csv)
+}
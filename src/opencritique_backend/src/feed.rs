@@ -0,0 +1,251 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+/// Oldest entries are dropped once a user's feed grows past this
+const MAX_FEED_LEN: usize = 500;
+const MAX_PAGE_SIZE: u64 = 100;
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum FollowTarget {
+    /// New artworks by this principal
+    Artist(Principal),
+    /// New critiques by this principal
+    Critic(Principal),
+    /// New artworks carrying this tag (lowercase)
+    Tag(String),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub enum FeedEvent {
+    NewArtwork { art_id: u64, title: String },
+    NewCritique { art_id: u64, critique_id: u64 },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct FeedItem {
+    /// Global, increasing sequence number (used as pagination cursor)
+    pub seq: u64,
+    pub timestamp: u64,
+    pub event: FeedEvent,
+    /// The followed source that put this item in the feed
+    pub source: FollowTarget,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct FeedPage {
+    pub items: Vec<FeedItem>,
+    /// Pass back to `get_feed` to load older items; None when the end is reached
+    pub next_cursor: Option<u64>,
+}
+
+thread_local! {
+    static FOLLOWERS: RefCell<HashMap<FollowTarget, HashSet<Principal>>> = RefCell::new(HashMap::new());
+    static FOLLOWING: RefCell<HashMap<Principal, Vec<FollowTarget>>> = RefCell::new(HashMap::new());
+    // Per-user fan-out inbox, oldest first
+    static FEEDS: RefCell<HashMap<Principal, Vec<FeedItem>>> = RefCell::new(HashMap::new());
+    static FEED_SEQ: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Upgrade snapshot of the follow graph and feeds
+#[derive(CandidType, Deserialize)]
+pub(crate) struct FeedState {
+    followers: HashMap<FollowTarget, HashSet<Principal>>,
+    following: HashMap<Principal, Vec<FollowTarget>>,
+    feeds: HashMap<Principal, Vec<FeedItem>>,
+    feed_seq: u64,
+}
+
+pub(crate) fn save_state() -> FeedState {
+    FeedState {
+        followers: FOLLOWERS.with(|s| s.take()),
+        following: FOLLOWING.with(|s| s.take()),
+        feeds: FEEDS.with(|s| s.take()),
+        feed_seq: FEED_SEQ.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: FeedState) {
+    FOLLOWERS.with(|s| *s.borrow_mut() = state.followers);
+    FOLLOWING.with(|s| *s.borrow_mut() = state.following);
+    FEEDS.with(|s| *s.borrow_mut() = state.feeds);
+    FEED_SEQ.with(|s| *s.borrow_mut() = state.feed_seq);
+}
+
+fn normalize(target: FollowTarget) -> FollowTarget {
+    match target {
+        FollowTarget::Tag(tag) => FollowTarget::Tag(tag.trim().to_lowercase()),
+        other => other,
+    }
+}
+
+/// Append an event to the feed of everyone following any of `sources`.
+/// A follower matched by several sources receives the event once.
+fn fan_out(sources: Vec<FollowTarget>, event: FeedEvent, exclude: Option<Principal>) {
+    let mut recipients: HashMap<Principal, FollowTarget> = HashMap::new();
+    FOLLOWERS.with(|f| {
+        let followers = f.borrow();
+        for source in sources {
+            for follower in followers.get(&source).into_iter().flatten() {
+                recipients.entry(*follower).or_insert_with(|| source.clone());
+            }
+        }
+    });
+    if let Some(excluded) = exclude {
+        recipients.remove(&excluded);
+    }
+    if recipients.is_empty() {
+        return;
+    }
+
+    let seq = FEED_SEQ.with(|s| {
+        let mut s = s.borrow_mut();
+        *s += 1;
+        *s
+    });
+    let timestamp = time();
+
+    FEEDS.with(|feeds| {
+        let mut feeds = feeds.borrow_mut();
+        for (follower, source) in recipients {
+            let feed = feeds.entry(follower).or_default();
+            feed.push(FeedItem {
+                seq,
+                timestamp,
+                event: event.clone(),
+                source,
+            });
+            if feed.len() > MAX_FEED_LEN {
+                let excess = feed.len() - MAX_FEED_LEN;
+                feed.drain(..excess);
+            }
+        }
+    });
+}
+
+/// Called when an artwork is published. Anonymous works only reach tag followers.
+pub(crate) fn publish_artwork(art_id: u64, title: &str, author: Principal, tags: &[String], anonymous: bool) {
+    let mut sources: Vec<FollowTarget> = Vec::new();
+    if !anonymous {
+        sources.push(FollowTarget::Artist(author));
+    }
+    sources.extend(tags.iter().map(|t| FollowTarget::Tag(t.trim().to_lowercase())));
+
+    let event = FeedEvent::NewArtwork { art_id, title: title.to_string() };
+    fan_out(sources, event, Some(author));
+}
+
+/// Called when a critique is posted. Anonymous critiques are not fanned out.
+pub(crate) fn publish_critique(art_id: u64, critique_id: u64, critic: Principal, anonymous: bool) {
+    if anonymous {
+        return;
+    }
+    let event = FeedEvent::NewCritique { art_id, critique_id };
+    fan_out(vec![FollowTarget::Critic(critic)], event, Some(critic));
+}
+
+/// Remove feed items that reached followers through the artwork's author,
+/// so making a work anonymous does not leave it linked to its artist
+pub(crate) fn retract_artist_items(art_id: u64) {
+    FEEDS.with(|feeds| {
+        for feed in feeds.borrow_mut().values_mut() {
+            feed.retain(|item| {
+                !matches!(
+                    (&item.event, &item.source),
+                    (FeedEvent::NewArtwork { art_id: id, .. }, FollowTarget::Artist(_)) if *id == art_id
+                )
+            });
+        }
+    });
+}
+
+#[update]
+pub fn follow(target: FollowTarget) -> Result<String, String> {
+    let user = caller();
+    if user == Principal::anonymous() {
+        return Err("Anonymous users cannot follow".to_string());
+    }
+
+    let target = normalize(target);
+    match &target {
+        FollowTarget::Artist(p) | FollowTarget::Critic(p) if *p == user => {
+            return Err("You cannot follow yourself".to_string());
+        }
+        FollowTarget::Tag(tag) if tag.is_empty() => {
+            return Err("Tag cannot be empty".to_string());
+        }
+        _ => {}
+    }
+
+    let inserted = FOLLOWERS.with(|f| {
+        f.borrow_mut().entry(target.clone()).or_default().insert(user)
+    });
+    if !inserted {
+        return Err("Already following".to_string());
+    }
+    FOLLOWING.with(|f| f.borrow_mut().entry(user).or_default().push(target));
+    Ok("Followed".to_string())
+}
+
+#[update]
+pub fn unfollow(target: FollowTarget) -> Result<String, String> {
+    let user = caller();
+    let target = normalize(target);
+
+    let removed = FOLLOWERS.with(|f| {
+        let mut followers = f.borrow_mut();
+        let removed = followers.get_mut(&target).map(|set| set.remove(&user)).unwrap_or(false);
+        if followers.get(&target).is_some_and(|set| set.is_empty()) {
+            followers.remove(&target);
+        }
+        removed
+    });
+    if !removed {
+        return Err("Not following".to_string());
+    }
+    FOLLOWING.with(|f| {
+        if let Some(list) = f.borrow_mut().get_mut(&user) {
+            list.retain(|t| *t != target);
+        }
+    });
+    Ok("Unfollowed".to_string())
+}
+
+#[query]
+pub fn get_following() -> Vec<FollowTarget> {
+    FOLLOWING.with(|f| f.borrow().get(&caller()).cloned().unwrap_or_default())
+}
+
+#[query]
+pub fn get_follower_count(target: FollowTarget) -> u64 {
+    let target = normalize(target);
+    FOLLOWERS.with(|f| f.borrow().get(&target).map(|set| set.len() as u64).unwrap_or(0))
+}
+
+/// The caller's feed, newest first. Pass the previous page's `next_cursor`
+/// to continue with older items.
+#[query]
+pub fn get_feed(cursor: Option<u64>, limit: u64) -> FeedPage {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+
+    FEEDS.with(|feeds| {
+        let feeds = feeds.borrow();
+        let Some(feed) = feeds.get(&caller()) else {
+            return FeedPage { items: vec![], next_cursor: None };
+        };
+
+        // Items are stored oldest first, so walk backwards from the cursor
+        let end = match cursor {
+            Some(c) => feed.partition_point(|item| item.seq < c),
+            None => feed.len(),
+        };
+        let start = end.saturating_sub(limit);
+        let items: Vec<FeedItem> = feed[start..end].iter().rev().cloned().collect();
+        let next_cursor = if start > 0 { items.last().map(|item| item.seq) } else { None };
+
+        FeedPage { items, next_cursor }
+    })
+}
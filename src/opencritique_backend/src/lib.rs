@@ -1,18 +1,53 @@
-use ic_cdk::api::{caller, time};
+use crate::runtime::{caller, time};
 // use ic_cdk_macros::*;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 // use ic_ledger_types::{AccountIdentifier, Subaccount};
 
-use ic_cdk::{update, query};
+use ic_cdk::{init, pre_upgrade, post_upgrade, update, query};
 // use ic_cdk::export::Principal;
 
 pub mod bounty;
 pub use bounty::*;
-
-const ADMIN: &str = "aaaaa-aa";
+pub mod reputation;
+pub use reputation::*;
+pub mod profile;
+pub use profile::*;
+pub mod anonymity;
+pub use anonymity::*;
+pub mod feed;
+pub use feed::*;
+pub mod notifications;
+pub use notifications::*;
+pub mod bounty_jobs;
+pub use bounty_jobs::*;
+pub mod bounty_claims;
+pub use bounty_claims::*;
+pub mod disputes;
+pub use disputes::*;
+pub mod critique_requests;
+pub use critique_requests::*;
+pub mod escrow_audit;
+pub use escrow_audit::*;
+pub mod deposits;
+pub use deposits::*;
+pub mod treasury;
+pub use treasury::*;
+#[cfg(feature = "mock-ledger")]
+pub mod mock_ledger;
+#[cfg(feature = "mock-ledger")]
+pub use mock_ledger::*;
+pub mod tokens;
+pub use tokens::*;
+pub mod config;
+pub use config::*;
+mod runtime;
+
+fn is_admin(principal: &Principal) -> bool {
+    crate::config::is_admin(principal)
+}
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub enum ResultText {
@@ -94,6 +129,8 @@ struct Artwork {
 
     author: Principal,
     username: String,
+    /// Legacy copy of the uploader's email. New uploads leave this empty and
+    /// `migrate_artwork_emails` moves old values into the private profile contact.
     email: String,
     tags: Vec<String>,
     feedback_bounty: u64,
@@ -139,6 +176,109 @@ struct Artwork {
     /// Creation timestamp (nanoseconds since UNIX epoch)
     #[serde(default)]
     created_at_ns: u64,
+
+    /// Posted anonymously: public views show the author's pseudonym instead of their principal
+    #[serde(default)]
+    anonymous: bool,
+
+    /// Deletion was requested while the bounty escrow still held funds. The
+    /// artwork stays hidden until the bounty sweep settles the escrow and removes it.
+    #[serde(default)]
+    deleted_at: Option<u64>,
+}
+
+impl Artwork {
+    /// Principals public views must not reveal: the author of an anonymous
+    /// work and the critics of anonymous critiques
+    fn anonymous_principals(&self) -> HashSet<Principal> {
+        let mut hidden: HashSet<Principal> = self.critiques
+            .iter()
+            .filter(|c| c.anonymous)
+            .map(|c| c.critic)
+            .collect();
+        if self.anonymous {
+            hidden.insert(self.author);
+        }
+        hidden
+    }
+
+    /// The bounty as shown to any caller
+    fn public_bounty(&self) -> Option<BountyView> {
+        self.bounty
+            .as_ref()
+            .map(|bounty| BountyView::redacted(bounty, &self.anonymous_principals(), self.anonymous))
+    }
+}
+
+/// Public representation of an artwork, without the author's personal data
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+struct ArtworkView {
+    id: u64,
+    title: String,
+    description: String,
+    image_url: String,
+    author: Principal,
+    username: String,
+    tags: Vec<String>,
+    feedback_bounty: u64,
+    license: String,
+    critiques: Vec<Critique>,
+    bounty: Option<BountyView>,
+    is_nft: bool,
+    nft_price: u64,
+    nft_buyer: String,
+    media_type: Option<String>,
+    main_cid: Option<String>,
+    thumbnail_cid: Option<String>,
+    preview_cid: Option<String>,
+    mime_type: Option<String>,
+    text_excerpt: Option<String>,
+    media_files: Vec<MediaFile>,
+    created_at_ns: u64,
+    anonymous: bool,
+    deleted_at: Option<u64>,
+}
+
+impl From<&Artwork> for ArtworkView {
+    fn from(art: &Artwork) -> Self {
+        // Anonymous works hide the principal behind a pseudonym; otherwise
+        // authors with a profile are shown under their current display name
+        let (author, username) = if art.anonymous {
+            (Principal::anonymous(), crate::anonymity::pseudonym_of(art.author))
+        } else {
+            match crate::profile::profile_of(&art.author) {
+                Some(profile) => (art.author, profile.display_name),
+                None => (art.author, art.username.clone()),
+            }
+        };
+
+        Self {
+            id: art.id,
+            title: art.title.clone(),
+            description: art.description.clone(),
+            image_url: art.image_url.clone(),
+            author,
+            username,
+            tags: art.tags.clone(),
+            feedback_bounty: art.feedback_bounty,
+            license: art.license.clone(),
+            critiques: art.critiques.iter().map(Critique::public).collect(),
+            bounty: art.public_bounty(),
+            is_nft: art.is_nft,
+            nft_price: art.nft_price,
+            nft_buyer: art.nft_buyer.clone(),
+            media_type: art.media_type.clone(),
+            main_cid: art.main_cid.clone(),
+            thumbnail_cid: art.thumbnail_cid.clone(),
+            preview_cid: art.preview_cid.clone(),
+            mime_type: art.mime_type.clone(),
+            text_excerpt: art.text_excerpt.clone(),
+            media_files: art.media_files.clone(),
+            created_at_ns: art.created_at_ns,
+            anonymous: art.anonymous,
+            deleted_at: art.deleted_at,
+        }
+    }
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
@@ -150,6 +290,27 @@ struct Critique {
     upvotes: u64,
     upvoters: Vec<Principal>,
     is_rewarded: Option<bool>,
+
+    /// Posted anonymously: public views show `pseudonym` instead of `critic`
+    #[serde(default)]
+    anonymous: bool,
+    #[serde(default)]
+    pseudonym: Option<String>,
+
+    /// Bounty payout received for this critique
+    #[serde(default)]
+    reward: Option<CritiqueReward>,
+}
+
+impl Critique {
+    /// Copy of the critique safe to return to any caller
+    fn public(&self) -> Critique {
+        let mut critique = self.clone();
+        if critique.anonymous {
+            critique.critic = Principal::anonymous();
+        }
+        critique
+    }
 }
 
 thread_local! {
@@ -158,6 +319,104 @@ thread_local! {
     static ART_ID_COUNTER: RefCell<u64> = RefCell::new(0);
 }
 
+/* ---------- Lifecycle: config and state are kept in stable memory, timers are re-armed ---------- */
+
+/// Canister state saved across upgrades, next to the config. Fields added
+/// later must be `Option`s so that state saved by older versions still decodes.
+#[derive(CandidType, Deserialize)]
+struct StableState {
+    artworks: Vec<Artwork>,
+    points: HashMap<Principal, u64>,
+    art_id_counter: u64,
+    reputation: crate::reputation::ReputationState,
+    profiles: crate::profile::ProfileState,
+    anonymity: crate::anonymity::AnonymityState,
+    feed: crate::feed::FeedState,
+    notifications: crate::notifications::NotificationState,
+    bounty_jobs: crate::bounty_jobs::BountyJobState,
+    claims: crate::bounty_claims::ClaimState,
+    critique_requests: crate::critique_requests::CritiqueRequestState,
+    escrow_audit: crate::escrow_audit::EscrowAuditState,
+    deposits: crate::deposits::DepositScanState,
+    treasury: crate::treasury::TreasuryState,
+    tokens: crate::tokens::TokenState,
+}
+
+impl StableState {
+    fn save() -> Self {
+        Self {
+            artworks: ARTWORKS.with(|a| a.take()),
+            points: POINTS.with(|p| p.take()),
+            art_id_counter: ART_ID_COUNTER.with(|c| c.take()),
+            reputation: crate::reputation::save_state(),
+            profiles: crate::profile::save_state(),
+            anonymity: crate::anonymity::save_state(),
+            feed: crate::feed::save_state(),
+            notifications: crate::notifications::save_state(),
+            bounty_jobs: crate::bounty_jobs::save_state(),
+            claims: crate::bounty_claims::save_state(),
+            critique_requests: crate::critique_requests::save_state(),
+            escrow_audit: crate::escrow_audit::save_state(),
+            deposits: crate::deposits::save_state(),
+            treasury: crate::treasury::save_state(),
+            tokens: crate::tokens::save_state(),
+        }
+    }
+
+    fn restore(self) {
+        ARTWORKS.with(|a| *a.borrow_mut() = self.artworks);
+        POINTS.with(|p| *p.borrow_mut() = self.points);
+        ART_ID_COUNTER.with(|c| *c.borrow_mut() = self.art_id_counter);
+        crate::reputation::restore_state(self.reputation);
+        crate::profile::restore_state(self.profiles);
+        crate::anonymity::restore_state(self.anonymity);
+        crate::feed::restore_state(self.feed);
+        crate::notifications::restore_state(self.notifications);
+        crate::bounty_jobs::restore_state(self.bounty_jobs);
+        crate::bounty_claims::restore_state(self.claims);
+        crate::critique_requests::restore_state(self.critique_requests);
+        crate::escrow_audit::restore_state(self.escrow_audit);
+        crate::deposits::restore_state(self.deposits);
+        crate::treasury::restore_state(self.treasury);
+        crate::tokens::restore_state(self.tokens);
+    }
+}
+
+#[init]
+fn init(args: Option<InitArgs>) {
+    crate::config::load(CanisterConfig::default(), args);
+    arm_timers();
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    ic_cdk::storage::stable_save((crate::config::config(), Some(StableState::save())))
+        .expect("Failed to save state to stable memory");
+}
+
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    // Canisters installed before the config existed have nothing in stable
+    // memory, and those upgraded before the state was saved only the config.
+    // Anything else that fails to decode aborts the upgrade instead of losing state.
+    let (stored, state) = if ic_cdk::api::stable::stable_size() == 0 {
+        (CanisterConfig::default(), None)
+    } else {
+        ic_cdk::storage::stable_restore::<(CanisterConfig, Option<StableState>)>()
+            .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to restore state from stable memory: {}", e)))
+    };
+    crate::config::load(stored, args);
+    if let Some(state) = state {
+        state.restore();
+    }
+    arm_timers();
+}
+
+fn arm_timers() {
+    crate::bounty_jobs::arm_bounty_jobs();
+    crate::reputation::rearm_timers();
+}
+
 /* ---------- Helper: infer media type from URL/CID and tags ---------- */
 
 fn infer_media_type(primary: &str, tags: &[String]) -> MediaType {
@@ -198,6 +457,25 @@ fn infer_media_type(primary: &str, tags: &[String]) -> MediaType {
     MediaType::Other
 }
 
+/* ---------- Helper: author names come from the profile registry ---------- */
+
+/// Authors with a profile are referenced by principal, so the name is not copied into the artwork
+fn profile_username(author: &Principal, username: String) -> String {
+    if crate::profile::profile_of(author).is_some() {
+        String::new()
+    } else {
+        username
+    }
+}
+
+/// Contact emails are kept in the private profile contact, never on the artwork
+fn record_contact_email(author: Principal, email: String) -> String {
+    if !email.trim().is_empty() {
+        crate::profile::set_contact_if_missing(author, email.trim().to_string());
+    }
+    String::new()
+}
+
 /* ---------- Updates & Queries (names/signatures unchanged) ---------- */
 
 #[update]
@@ -219,6 +497,8 @@ async fn upload_art(
 ) -> Result<String, String> {
     let author = caller();
     ic_cdk::println!("DEBUG: Caller principal: {}", author.to_text());
+    let username = profile_username(&author, username);
+    let email = record_contact_email(author, email);
     let art_id = ART_ID_COUNTER.with(|counter| {
         let mut id = counter.borrow_mut();
         *id += 1;
@@ -268,6 +548,8 @@ async fn upload_art(
         text_excerpt,
         media_files,
         created_at_ns: time(),
+        anonymous: false,
+        deleted_at: None,
     };
 
     // ✅ CRITICAL FIX: Create escrow account during upload
@@ -287,6 +569,7 @@ async fn upload_art(
         }
     }
 
+    crate::feed::publish_artwork(art_id, &new_art.title, author, &new_art.tags, new_art.anonymous);
     ARTWORKS.with(|arts| arts.borrow_mut().push(new_art));
     Ok(format!("Artwork {} uploaded successfully with escrow account", art_id))
 }
@@ -311,12 +594,20 @@ fn upload_art_with_principal(
     author_principal: Principal, // New parameter
 ) {
     let author = author_principal; // Use passed principal
+    let username = profile_username(&author, username);
     
     // Debug logging
     let caller_principal = caller();
     ic_cdk::println!("DEBUG: Caller: {}, Passed author: {}", 
         caller_principal.to_text(), author.to_text());
 
+    // Only the author themselves may set their contact email
+    let email = if caller_principal == author {
+        record_contact_email(author, email)
+    } else {
+        String::new()
+    };
+
     let art_id = ART_ID_COUNTER.with(|counter| {
         let mut id = counter.borrow_mut();
         *id += 1;
@@ -365,22 +656,33 @@ fn upload_art_with_principal(
         text_excerpt,
         media_files,
         created_at_ns: time(),
+        anonymous: false,
+        deleted_at: None,
     };
 
     if feedback_bounty > 0 {
         new_art.bounty = crate::bounty::set_artwork_bounty(art_id, feedback_bounty, author);
     }
 
+    // Feed items are attributed to the author, so only they may publish one
+    if caller_principal == author {
+        crate::feed::publish_artwork(art_id, &new_art.title, author, &new_art.tags, new_art.anonymous);
+    }
     ARTWORKS.with(|arts| arts.borrow_mut().push(new_art));
 }
 
 #[update]
 fn post_critique(art_id: u64, text: String) {
+    add_critique(art_id, text, false);
+}
+
+fn add_critique(art_id: u64, text: String, anonymous: bool) {
     let critic = caller();
+    let pseudonym = anonymous.then(|| crate::anonymity::pseudonym_of(critic));
 
     ARTWORKS.with(|arts| {
         let mut artworks = arts.borrow_mut();
-        if let Some(art) = artworks.iter_mut().find(|a| a.id == art_id) {
+        if let Some(art) = artworks.iter_mut().find(|a| a.id == art_id && a.deleted_at.is_none()) {
             let critique_id = art.critiques.len() as u64;
             let new_critique = Critique {
                 id: critique_id,
@@ -390,13 +692,14 @@ fn post_critique(art_id: u64, text: String) {
                 upvotes: 0,
                 upvoters: vec![],
                 is_rewarded: Some(false),
+                anonymous,
+                pseudonym,
+                reward: None,
             };
             art.critiques.push(new_critique);
 
-            POINTS.with(|pts| {
-                let mut points = pts.borrow_mut();
-                *points.entry(critic).or_insert(0) += 1;
-            });
+            crate::reputation::award_points(critic, 1);
+            crate::feed::publish_critique(art_id, critique_id, critic, anonymous);
         }
     });
 }
@@ -413,10 +716,8 @@ fn upvote_critique(art_id: u64, critique_id: u64) {
                     cri.upvotes += 1;
                     cri.upvoters.push(voter);
 
-                    POINTS.with(|pts| {
-                        let mut points = pts.borrow_mut();
-                        *points.entry(cri.critic).or_insert(0) += 1;
-                    });
+                    crate::reputation::award_points(cri.critic, 1);
+                    crate::reputation::touch_activity(voter);
                 }
             }
         }
@@ -424,8 +725,14 @@ fn upvote_critique(art_id: u64, critique_id: u64) {
 }
 
 #[query]
-fn get_artworks() -> Vec<Artwork> {
-    ARTWORKS.with(|arts| arts.borrow().clone())
+fn get_artworks() -> Vec<ArtworkView> {
+    ARTWORKS.with(|arts| {
+        arts.borrow()
+            .iter()
+            .filter(|a| a.deleted_at.is_none())
+            .map(ArtworkView::from)
+            .collect()
+    })
 }
 
 #[query]
@@ -434,7 +741,7 @@ fn get_critiques(art_id: u64) -> Vec<Critique> {
         arts.borrow()
             .iter()
             .find(|a| a.id == art_id)
-            .map(|a| a.critiques.clone())
+            .map(|a| a.critiques.iter().map(Critique::public).collect())
             .unwrap_or_else(Vec::new)
     })
 }
@@ -446,11 +753,13 @@ fn get_points(user: Principal) -> u64 {
 
 #[query]
 fn get_critiques_for_user(user: Principal) -> Vec<Critique> {
+    // Anonymous critiques are only listed for their own author
+    let include_anonymous = caller() == user;
     let mut result = vec![];
     ARTWORKS.with(|arts| {
         for art in arts.borrow().iter() {
             for cri in &art.critiques {
-                if cri.critic == user {
+                if cri.critic == user && (include_anonymous || !cri.anonymous) {
                     result.push(cri.clone());
                 }
             }
@@ -474,9 +783,22 @@ fn delete_artwork(art_id: u64) -> ResultText{
         let mut artworks = arts.borrow_mut();
         if let Some(index) = artworks.iter().position(|a| a.id == art_id) {
             let artwork = &artworks[index];
-            let admin_principal: Principal = Principal::from_text(ADMIN).unwrap();
 
-            if caller_id == artwork.author || caller_id == admin_principal {
+            if caller_id == artwork.author || is_admin(&caller_id) {
+                // The escrow may hold funds the records do not show yet, e.g.
+                // unscanned deposits: close the bounty and hide the artwork, and
+                // let the bounty sweep remove it once the ledger shows it empty
+                if artwork.bounty.is_some() {
+                    let artwork = &mut artworks[index];
+                    artwork.deleted_at.get_or_insert_with(time);
+                    if let Some(bounty) = artwork.bounty.as_mut() {
+                        bounty.close("artwork deleted");
+                    }
+                    return ResultText::Ok(format!(
+                        "Artwork {} hidden. It will be deleted once its bounty escrow is refunded or paid out.",
+                        art_id
+                    ));
+                }
                 artworks.remove(index);
                 ResultText::Ok(format!("Artwork {} deleted.", art_id))
             } else {
@@ -489,13 +811,13 @@ fn delete_artwork(art_id: u64) -> ResultText{
 }
 
 #[query]
-fn get_my_artworks() -> Vec<Artwork> {
+fn get_my_artworks() -> Vec<ArtworkView> {
     let my_id = caller();
     ARTWORKS.with(|arts| {
         arts.borrow()
             .iter()
             .filter(|a| a.author == my_id)
-            .cloned()
+            .map(ArtworkView::from)
             .collect()
     })
 }
@@ -503,24 +825,46 @@ fn get_my_artworks() -> Vec<Artwork> {
 #[query]
 fn get_my_artworks_using_principal(
     author_principal: Principal,
-) -> Vec<Artwork> {
+) -> Vec<ArtworkView> {
     // let my_id = caller();
     let my_id = author_principal; // Use passed principal
+    // Anonymous works are only listed for their own author
+    let include_anonymous = caller() == my_id;
     ARTWORKS.with(|arts| {
         arts.borrow()
             .iter()
-            .filter(|a| a.author == my_id)
-            .cloned()
+            .filter(|a| a.author == my_id && a.deleted_at.is_none() && (include_anonymous || !a.anonymous))
+            .map(ArtworkView::from)
             .collect()
     })
 }
 
 /******************* NFT **************************************/
 
+/// Buy an NFT. The buyer first approves the price plus one ledger fee for this
+/// canister on the ICP ledger; the platform fee is kept in the treasury and
+/// the rest paid to the artist.
 #[update]
-fn set_nft_buyer(artwork_id: u64) -> Result<String, String> {
+async fn set_nft_buyer(artwork_id: u64) -> Result<String, String> {
     let caller_principal = caller();
-    
+    // Held until the buyer is recorded, so two purchases cannot both pay
+    let _lock = crate::treasury::NftSaleLock::acquire(artwork_id)?;
+
+    let (seller, price) = ARTWORKS.with(|artworks| {
+        let artworks = artworks.borrow();
+        match artworks.iter().find(|artwork| artwork.id == artwork_id && artwork.deleted_at.is_none()) {
+            Some(artwork) => {
+                if !artwork.nft_buyer.is_empty() && artwork.nft_buyer != "0" {
+                    return Err("This NFT has already been purchased".to_string());
+                }
+                Ok((artwork.author, artwork.nft_price))
+            }
+            None => Err("Artwork not found".to_string()),
+        }
+    })?;
+
+    let sale = crate::treasury::collect_nft_payment(artwork_id, caller_principal, seller, price).await?;
+
     ARTWORKS.with(|artworks| {
         let mut artworks = artworks.borrow_mut();
         
@@ -530,17 +874,18 @@ fn set_nft_buyer(artwork_id: u64) -> Result<String, String> {
                     artwork.is_nft= true;
                 }
                 
-                if !artwork.nft_buyer.is_empty() && artwork.nft_buyer != "0" && artwork.nft_buyer != "" {
-                    return Err("This NFT has already been purchased".to_string());
-                }
-                
                 // Use caller automatically
                 artwork.nft_buyer = caller_principal.to_text();
                 
+                let payment = match sale {
+                    Some(sale) => format!(" (payment block {})", sale.payment_block),
+                    None => String::new(),
+                };
                 Ok(format!(
-                    "Successfully purchased NFT '{}' for buyer: {}", 
+                    "Successfully purchased NFT '{}' for buyer: {}{}", 
                     artwork.title, 
-                    artwork.nft_buyer
+                    artwork.nft_buyer,
+                    payment
                 ))
             }
             None => Err("Artwork not found".to_string()),
@@ -549,15 +894,15 @@ fn set_nft_buyer(artwork_id: u64) -> Result<String, String> {
 }
 
 #[query]
-fn get_user_nfts(user_principal: Principal) -> Vec<Artwork> {
+fn get_user_nfts(user_principal: Principal) -> Vec<ArtworkView> {
     ARTWORKS.with(|artworks| {
         let artworks = artworks.borrow();
         let user_text = user_principal.to_text();
         
         artworks
             .iter()  // Use .iter() instead of .values() for Vec
-            .filter(|artwork| artwork.is_nft && artwork.nft_buyer == user_text)
-            .cloned()
+            .filter(|artwork| artwork.is_nft && artwork.nft_buyer == user_text && artwork.deleted_at.is_none())
+            .map(ArtworkView::from)
             .collect()
     })
 }
@@ -589,7 +934,7 @@ fn mint_nft(artwork_id: u64, nft_price: u64) -> Result<String, String> {
         let mut artworks = artworks.borrow_mut();
         
         // Find artwork by id in the Vec
-        match artworks.iter_mut().find(|artwork| artwork.id == artwork_id) {
+        match artworks.iter_mut().find(|artwork| artwork.id == artwork_id && artwork.deleted_at.is_none()) {
             Some(artwork) => {
                 // Check if caller is the author of the artwork
                 if artwork.author != caller_principal {
@@ -620,24 +965,27 @@ fn mint_nft(artwork_id: u64, nft_price: u64) -> Result<String, String> {
 /**************************************************************/
 
 #[query]
-fn get_artwork_by_id(id: u64) -> Vec<Artwork> {
+fn get_artwork_by_id(id: u64) -> Vec<ArtworkView> {
     ARTWORKS.with(|artworks| {
         artworks.borrow()
             .iter()
-            .filter(|artwork| artwork.id == id)
-            .cloned()
+            .filter(|artwork| artwork.id == id && artwork.deleted_at.is_none())
+            .map(ArtworkView::from)
             .collect()
     })
 }
 
 // Optional: Batch function for better performance
 #[query]
-fn get_artworks_by_ids(ids: Vec<u64>) -> Vec<Artwork> {
+fn get_artworks_by_ids(ids: Vec<u64>) -> Vec<ArtworkView> {
     ARTWORKS.with(|artworks| {
         let artwork_list = artworks.borrow();
         ids.into_iter()
             .filter_map(|id| {
-                artwork_list.iter().find(|artwork| artwork.id == id).cloned()
+                artwork_list
+                    .iter()
+                    .find(|artwork| artwork.id == id && artwork.deleted_at.is_none())
+                    .map(ArtworkView::from)
             })
             .collect()
     })
@@ -0,0 +1,386 @@
+use ic_cdk::api::call::{CallResult, RejectionCode};
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc::generic_metadata_value::MetadataValue;
+use icrc_ledger_types::icrc1::account::Account;
+use icrc_ledger_types::icrc1::transfer::{TransferArg, TransferError};
+use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::config::is_testing;
+
+// Built-in stand-in for the ICRC ledgers, compiled only with the `mock-ledger`
+// feature and used instead of inter-canister calls in the Testing environment.
+// It keeps balances, allowances and block heights per ledger principal, charges
+// the token fee and answers with the same errors as a real ledger.
+
+// Real ledgers reject transactions created longer ago than this
+const TRANSACTION_WINDOW_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+thread_local! {
+    static BALANCES: RefCell<BTreeMap<(Principal, Account), u64>> = const { RefCell::new(BTreeMap::new()) };
+    // (ledger, owner, spender) -> remaining allowance
+    static ALLOWANCES: RefCell<BTreeMap<(Principal, Account, Account), u64>> = const { RefCell::new(BTreeMap::new()) };
+    static CHAIN_LENGTH: RefCell<BTreeMap<Principal, u64>> = const { RefCell::new(BTreeMap::new()) };
+    // Transactions with created_at_time, for duplicate detection
+    static SEEN: RefCell<BTreeMap<(Principal, String), u64>> = const { RefCell::new(BTreeMap::new()) };
+    static FAIL_NEXT_CALLS: RefCell<u32> = const { RefCell::new(0) };
+}
+
+fn balance(ledger: Principal, account: Account) -> u64 {
+    BALANCES.with(|b| b.borrow().get(&(ledger, account)).copied().unwrap_or(0))
+}
+
+fn set_balance(ledger: Principal, account: Account, amount: u64) {
+    BALANCES.with(|b| b.borrow_mut().insert((ledger, account), amount));
+}
+
+fn allowance(ledger: Principal, owner: Account, spender: Account) -> u64 {
+    ALLOWANCES.with(|a| a.borrow().get(&(ledger, owner, spender)).copied().unwrap_or(0))
+}
+
+fn next_block(ledger: Principal) -> u64 {
+    CHAIN_LENGTH.with(|c| {
+        let mut chains = c.borrow_mut();
+        let length = chains.entry(ledger).or_insert(0);
+        *length += 1;
+        *length - 1
+    })
+}
+
+fn to_u64(n: &Nat) -> u64 {
+    u64::try_from(n.0.clone()).unwrap_or(u64::MAX)
+}
+
+// An injected failure looks like a rejected inter-canister call: nothing is applied
+fn injected_failure() -> CallResult<()> {
+    FAIL_NEXT_CALLS.with(|f| {
+        let mut remaining = f.borrow_mut();
+        if *remaining == 0 {
+            return Ok(());
+        }
+        *remaining -= 1;
+        Err((RejectionCode::SysTransient, "mock ledger: injected failure".to_string()))
+    })
+}
+
+// The block of an earlier identical transaction, or why this one is too old
+fn check_duplicate(ledger: Principal, created_at_time: Option<u64>, key: &str) -> Result<(), Result<u64, ()>> {
+    let Some(created_at_time) = created_at_time else { return Ok(()) };
+    if created_at_time + TRANSACTION_WINDOW_NS < time() {
+        return Err(Err(()));
+    }
+    match SEEN.with(|s| s.borrow().get(&(ledger, key.to_string())).copied()) {
+        Some(block) => Err(Ok(block)),
+        None => Ok(()),
+    }
+}
+
+fn remember(ledger: Principal, created_at_time: Option<u64>, key: String, block: u64) {
+    if created_at_time.is_some() {
+        SEEN.with(|s| s.borrow_mut().insert((ledger, key), block));
+    }
+}
+
+pub(crate) fn icrc1_balance_of(ledger: Principal, account: Account) -> CallResult<(Nat,)> {
+    injected_failure()?;
+    Ok((balance(ledger, account).into(),))
+}
+
+pub(crate) fn icrc1_fee(ledger: Principal) -> CallResult<(Nat,)> {
+    injected_failure()?;
+    Ok((crate::tokens::token_fee(ledger).into(),))
+}
+
+pub(crate) fn icrc1_metadata(ledger: Principal) -> CallResult<(Vec<(String, MetadataValue)>,)> {
+    injected_failure()?;
+    Ok((vec![
+        ("icrc1:symbol".to_string(), MetadataValue::Text("TEST".to_string())),
+        ("icrc1:name".to_string(), MetadataValue::Text(format!("Mock token {}", ledger.to_text()))),
+        ("icrc1:decimals".to_string(), MetadataValue::Nat(8u64.into())),
+        ("icrc1:fee".to_string(), MetadataValue::Nat(crate::tokens::token_fee(ledger).into())),
+    ],))
+}
+
+/// `icrc1_transfer` as called by `owner`
+pub(crate) fn icrc1_transfer(ledger: Principal, owner: Principal, arg: TransferArg) -> CallResult<(Result<Nat, TransferError>,)> {
+    injected_failure()?;
+    let from = Account { owner, subaccount: arg.from_subaccount };
+    let key = format!("{:?} {:?}", from, arg);
+    let result = match check_duplicate(ledger, arg.created_at_time, &key) {
+        Err(Ok(block)) => Err(TransferError::Duplicate { duplicate_of: block.into() }),
+        Err(Err(())) => Err(TransferError::TooOld),
+        Ok(()) => {
+            let fee = crate::tokens::token_fee(ledger);
+            let amount = to_u64(&arg.amount);
+            let available = balance(ledger, from);
+            if arg.fee.as_ref().is_some_and(|f| to_u64(f) != fee) {
+                Err(TransferError::BadFee { expected_fee: fee.into() })
+            } else if available < amount.saturating_add(fee) {
+                Err(TransferError::InsufficientFunds { balance: available.into() })
+            } else {
+                set_balance(ledger, from, available - amount - fee);
+                set_balance(ledger, arg.to, balance(ledger, arg.to) + amount);
+                let block = next_block(ledger);
+                remember(ledger, arg.created_at_time, key, block);
+                Ok(block.into())
+            }
+        }
+    };
+    Ok((result,))
+}
+
+/// `icrc2_transfer_from` as called by `spender`
+pub(crate) fn icrc2_transfer_from(ledger: Principal, spender: Principal, args: TransferFromArgs) -> CallResult<(Result<Nat, TransferFromError>,)> {
+    injected_failure()?;
+    let spender = Account { owner: spender, subaccount: args.spender_subaccount };
+    let key = format!("{:?} {:?}", spender, args);
+    let result = match check_duplicate(ledger, args.created_at_time, &key) {
+        Err(Ok(block)) => Err(TransferFromError::Duplicate { duplicate_of: block.into() }),
+        Err(Err(())) => Err(TransferFromError::TooOld),
+        Ok(()) => {
+            let fee = crate::tokens::token_fee(ledger);
+            let amount = to_u64(&args.amount);
+            let available = balance(ledger, args.from);
+            let allowed = allowance(ledger, args.from, spender);
+            if args.fee.as_ref().is_some_and(|f| to_u64(f) != fee) {
+                Err(TransferFromError::BadFee { expected_fee: fee.into() })
+            } else if allowed < amount.saturating_add(fee) {
+                Err(TransferFromError::InsufficientAllowance { allowance: allowed.into() })
+            } else if available < amount.saturating_add(fee) {
+                Err(TransferFromError::InsufficientFunds { balance: available.into() })
+            } else {
+                ALLOWANCES.with(|a| a.borrow_mut().insert((ledger, args.from, spender), allowed - amount - fee));
+                set_balance(ledger, args.from, available - amount - fee);
+                set_balance(ledger, args.to, balance(ledger, args.to) + amount);
+                let block = next_block(ledger);
+                remember(ledger, args.created_at_time, key, block);
+                Ok(block.into())
+            }
+        }
+    };
+    Ok((result,))
+}
+
+fn require_testing() -> Result<(), String> {
+    if !is_testing() {
+        return Err("The mock ledger is only available in the Testing environment".to_string());
+    }
+    Ok(())
+}
+
+/// Create tokens on the mock ledger `ledger` (test builds only)
+#[update]
+pub fn mock_ledger_mint(ledger: Principal, to: Account, amount: u64) -> Result<u64, String> {
+    require_testing()?;
+    set_balance(ledger, to, balance(ledger, to) + amount);
+    Ok(next_block(ledger))
+}
+
+/// Transfer from the caller's default account, like a wallet sending to an
+/// escrow address (test builds only)
+#[update]
+pub fn mock_ledger_transfer(ledger: Principal, to: Account, amount: u64) -> Result<u64, String> {
+    require_testing()?;
+    let arg = TransferArg {
+        from_subaccount: None,
+        to,
+        fee: None,
+        created_at_time: None,
+        memo: None,
+        amount: amount.into(),
+    };
+    match icrc1_transfer(ledger, caller(), arg).map_err(|(_, msg)| msg)?.0 {
+        Ok(block) => Ok(to_u64(&block)),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+/// Let this canister spend `amount` (plus fees) from the caller's default
+/// account, as `icrc2_approve` would. Charges the approval fee (test builds only).
+#[update]
+pub fn mock_ledger_approve(ledger: Principal, amount: u64) -> Result<u64, String> {
+    require_testing()?;
+    let owner = Account::from(caller());
+    let fee = crate::tokens::token_fee(ledger);
+    let available = balance(ledger, owner);
+    if available < fee {
+        return Err(format!("{:?}", TransferError::InsufficientFunds { balance: available.into() }));
+    }
+    set_balance(ledger, owner, available - fee);
+    ALLOWANCES.with(|a| a.borrow_mut().insert((ledger, owner, Account::from(crate::runtime::id())), amount));
+    Ok(next_block(ledger))
+}
+
+/// Reject the next `count` calls to the mock ledger, as a busy or stopped
+/// ledger canister would, without applying them (test builds only)
+#[update]
+pub fn mock_ledger_fail_next_calls(count: u32) -> Result<(), String> {
+    require_testing()?;
+    FAIL_NEXT_CALLS.with(|f| *f.borrow_mut() = count);
+    Ok(())
+}
+
+#[query]
+pub fn mock_ledger_balance_of(ledger: Principal, account: Account) -> u64 {
+    balance(ledger, account)
+}
+
+/// Test harness for driving the canister endpoints against the mock ledger
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::config::{CanisterConfig, Environment};
+    pub(crate) use crate::runtime::{advance_time, block_on, set_caller};
+
+    /// Switch to the Testing environment, where ledger calls go to the mock
+    pub(crate) fn use_mock_ledger() -> Principal {
+        crate::config::load(CanisterConfig { environment: Environment::Testing, ..Default::default() }, None);
+        crate::config::ledger_canister_id()
+    }
+
+    pub(crate) fn mint(ledger: Principal, to: Principal, amount: u64) {
+        set_balance(ledger, Account::from(to), balance(ledger, Account::from(to)) + amount);
+    }
+
+    pub(crate) fn balance_of(ledger: Principal, account: Account) -> u64 {
+        balance(ledger, account)
+    }
+
+    pub(crate) fn fail_next_calls(count: u32) {
+        FAIL_NEXT_CALLS.with(|f| *f.borrow_mut() = count);
+    }
+
+    /// Upload an artwork with a bounty of `amount` as `author`
+    pub(crate) fn bounty_artwork(author: Principal, amount: u64) -> u64 {
+        set_caller(author);
+        let uploaded = block_on(crate::upload_art(
+            "Study".to_string(),
+            String::new(),
+            "https://example.com/study.png".to_string(),
+            "author".to_string(),
+            String::new(),
+            vec![],
+            amount,
+            "CC-BY".to_string(),
+            None,
+            None,
+            None,
+            false,
+            0,
+            String::new(),
+        ));
+        assert!(uploaded.is_ok());
+        crate::ARTWORKS.with(|arts| arts.borrow().last().map(|a| a.id)).unwrap()
+    }
+
+    /// Approve and pull `amount` from `author` into the bounty escrow
+    pub(crate) fn fund(ledger: Principal, author: Principal, artwork_id: u64, amount: u64) -> crate::bounty::BountyResult {
+        set_caller(author);
+        let fee = crate::tokens::token_fee(ledger);
+        assert!(mock_ledger_approve(ledger, amount + fee).is_ok());
+        block_on(crate::bounty::fund_bounty_with_approval(artwork_id, amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEDGER: Principal = Principal::from_slice(&[7; 10]);
+    const CANISTER: Principal = Principal::from_slice(&[1; 10]);
+
+    fn user(n: u8) -> Account {
+        Account::from(Principal::from_slice(&[n; 29]))
+    }
+
+    fn escrow() -> Account {
+        Account { owner: CANISTER, subaccount: Some([9; 32]) }
+    }
+
+    fn transfer(from: Principal, to: Account, amount: u64) -> Result<Nat, TransferError> {
+        let arg = TransferArg {
+            from_subaccount: None,
+            to,
+            fee: None,
+            created_at_time: None,
+            memo: None,
+            amount: amount.into(),
+        };
+        icrc1_transfer(LEDGER, from, arg).unwrap().0
+    }
+
+    fn transfer_from(from: Account, to: Account, amount: u64) -> Result<Nat, TransferFromError> {
+        let args = TransferFromArgs {
+            spender_subaccount: None,
+            from,
+            to,
+            amount: amount.into(),
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        };
+        icrc2_transfer_from(LEDGER, CANISTER, args).unwrap().0
+    }
+
+    #[test]
+    fn funding_an_escrow_charges_the_fee() {
+        let fee = crate::tokens::token_fee(LEDGER);
+        set_balance(LEDGER, user(1), 1_000_000);
+        assert!(transfer(user(1).owner, escrow(), 400_000).is_ok());
+        assert_eq!(balance(LEDGER, user(1)), 600_000 - fee);
+        assert_eq!(balance(LEDGER, escrow()), 400_000);
+    }
+
+    #[test]
+    fn transfers_fail_without_funds_or_with_a_wrong_fee() {
+        let fee = crate::tokens::token_fee(LEDGER);
+        set_balance(LEDGER, user(2), 100_000);
+        assert!(matches!(transfer(user(2).owner, escrow(), 100_000), Err(TransferError::InsufficientFunds { .. })));
+
+        let arg = TransferArg {
+            from_subaccount: None,
+            to: escrow(),
+            fee: Some((fee + 1).into()),
+            created_at_time: None,
+            memo: None,
+            amount: 1_000u64.into(),
+        };
+        let result = icrc1_transfer(LEDGER, user(2).owner, arg).unwrap().0;
+        assert!(matches!(result, Err(TransferError::BadFee { .. })));
+        assert_eq!(balance(LEDGER, user(2)), 100_000);
+    }
+
+    #[test]
+    fn transfer_from_needs_an_allowance_covering_the_fee() {
+        let fee = crate::tokens::token_fee(LEDGER);
+        let spender = Account::from(CANISTER);
+        set_balance(LEDGER, user(3), 1_000_000);
+        assert!(matches!(transfer_from(user(3), escrow(), 500_000), Err(TransferFromError::InsufficientAllowance { .. })));
+
+        ALLOWANCES.with(|a| a.borrow_mut().insert((LEDGER, user(3), spender), 500_000));
+        assert!(matches!(transfer_from(user(3), escrow(), 500_000), Err(TransferFromError::InsufficientAllowance { .. })));
+        assert!(transfer_from(user(3), escrow(), 500_000 - fee).is_ok());
+        assert_eq!(allowance(LEDGER, user(3), spender), 0);
+        assert_eq!(balance(LEDGER, user(3)), 500_000);
+    }
+
+    #[test]
+    fn injected_failures_apply_nothing() {
+        set_balance(LEDGER, user(4), 1_000_000);
+        FAIL_NEXT_CALLS.with(|f| *f.borrow_mut() = 1);
+        let arg = TransferArg {
+            from_subaccount: None,
+            to: user(5),
+            fee: None,
+            created_at_time: None,
+            memo: None,
+            amount: 1_000u64.into(),
+        };
+        assert!(icrc1_transfer(LEDGER, user(4).owner, arg).is_err());
+        assert_eq!(balance(LEDGER, user(4)), 1_000_000);
+        assert!(transfer(user(4).owner, user(5), 1_000).is_ok());
+    }
+}
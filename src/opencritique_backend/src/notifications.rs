@@ -0,0 +1,99 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Oldest notifications are dropped once a user's inbox grows past this
+const MAX_NOTIFICATIONS: usize = 200;
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Notification {
+    pub id: u64,
+    pub created_at: u64,
+    pub artwork_id: Option<u64>,
+    pub message: String,
+    pub read: bool,
+}
+
+thread_local! {
+    static NOTIFICATIONS: RefCell<HashMap<Principal, Vec<Notification>>> = RefCell::new(HashMap::new());
+    static NOTIFICATION_ID: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Upgrade snapshot of notification inboxes
+#[derive(CandidType, Deserialize)]
+pub(crate) struct NotificationState {
+    notifications: HashMap<Principal, Vec<Notification>>,
+    next_id: u64,
+}
+
+pub(crate) fn save_state() -> NotificationState {
+    NotificationState {
+        notifications: NOTIFICATIONS.with(|s| s.take()),
+        next_id: NOTIFICATION_ID.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: NotificationState) {
+    NOTIFICATIONS.with(|s| *s.borrow_mut() = state.notifications);
+    NOTIFICATION_ID.with(|s| *s.borrow_mut() = state.next_id);
+}
+
+/// Add a notification to a user's inbox
+pub(crate) fn notify(user: Principal, artwork_id: Option<u64>, message: String) {
+    let id = NOTIFICATION_ID.with(|n| {
+        let mut n = n.borrow_mut();
+        *n += 1;
+        *n
+    });
+
+    NOTIFICATIONS.with(|inboxes| {
+        let mut inboxes = inboxes.borrow_mut();
+        let inbox = inboxes.entry(user).or_default();
+        inbox.push(Notification {
+            id,
+            created_at: time(),
+            artwork_id,
+            message,
+            read: false,
+        });
+        if inbox.len() > MAX_NOTIFICATIONS {
+            let excess = inbox.len() - MAX_NOTIFICATIONS;
+            inbox.drain(..excess);
+        }
+    });
+}
+
+/// The caller's notifications, newest first
+#[query]
+pub fn get_notifications(unread_only: bool) -> Vec<Notification> {
+    NOTIFICATIONS.with(|inboxes| {
+        inboxes.borrow()
+            .get(&caller())
+            .map(|inbox| {
+                inbox.iter()
+                    .rev()
+                    .filter(|n| !unread_only || !n.read)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// Mark the caller's notifications up to and including `up_to_id` as read
+#[update]
+pub fn mark_notifications_read(up_to_id: u64) -> u64 {
+    NOTIFICATIONS.with(|inboxes| {
+        let mut marked = 0;
+        if let Some(inbox) = inboxes.borrow_mut().get_mut(&caller()) {
+            for n in inbox.iter_mut().filter(|n| n.id <= up_to_id && !n.read) {
+                n.read = true;
+                marked += 1;
+            }
+        }
+        marked
+    })
+}
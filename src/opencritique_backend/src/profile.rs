@@ -0,0 +1,239 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+const MAX_DISPLAY_NAME_LEN: usize = 50;
+const MAX_BIO_LEN: usize = 500;
+const MAX_LINKS: usize = 5;
+
+/// Public profile of a user, keyed by principal
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct UserProfile {
+    pub principal: Principal,
+    pub display_name: String,
+    /// Unique, lowercase handle (e.g. "night_owl")
+    pub handle: String,
+    pub bio: String,
+    pub avatar_cid: Option<String>,
+    pub links: Vec<String>,
+    pub preferred_media_types: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Editable part of a profile, used by `register_profile` and `update_profile`
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ProfileInput {
+    pub display_name: String,
+    pub handle: String,
+    pub bio: String,
+    pub avatar_cid: Option<String>,
+    pub links: Vec<String>,
+    pub preferred_media_types: Vec<String>,
+}
+
+thread_local! {
+    static PROFILES: RefCell<HashMap<Principal, UserProfile>> = RefCell::new(HashMap::new());
+    // lowercase handle -> owner
+    static HANDLES: RefCell<HashMap<String, Principal>> = RefCell::new(HashMap::new());
+    // Private contact emails, kept out of `UserProfile` so public queries cannot expose them
+    static CONTACTS: RefCell<HashMap<Principal, String>> = RefCell::new(HashMap::new());
+}
+
+/// Upgrade snapshot of profiles, handles and contacts
+#[derive(CandidType, Deserialize)]
+pub(crate) struct ProfileState {
+    profiles: HashMap<Principal, UserProfile>,
+    handles: HashMap<String, Principal>,
+    contacts: HashMap<Principal, String>,
+}
+
+pub(crate) fn save_state() -> ProfileState {
+    ProfileState {
+        profiles: PROFILES.with(|s| s.take()),
+        handles: HANDLES.with(|s| s.take()),
+        contacts: CONTACTS.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: ProfileState) {
+    PROFILES.with(|s| *s.borrow_mut() = state.profiles);
+    HANDLES.with(|s| *s.borrow_mut() = state.handles);
+    CONTACTS.with(|s| *s.borrow_mut() = state.contacts);
+}
+
+fn normalize_handle(handle: &str) -> Result<String, String> {
+    let handle = handle.trim().to_lowercase();
+    if handle.len() < 3 || handle.len() > 30 {
+        return Err("Handle must be between 3 and 30 characters".to_string());
+    }
+    if !handle.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("Handle may only contain letters, digits and underscores".to_string());
+    }
+    Ok(handle)
+}
+
+fn validate_input(input: &ProfileInput) -> Result<String, String> {
+    let name = input.display_name.trim();
+    if name.is_empty() || name.len() > MAX_DISPLAY_NAME_LEN {
+        return Err(format!("Display name must be between 1 and {} characters", MAX_DISPLAY_NAME_LEN));
+    }
+    if input.bio.len() > MAX_BIO_LEN {
+        return Err(format!("Bio cannot exceed {} characters", MAX_BIO_LEN));
+    }
+    if input.links.len() > MAX_LINKS {
+        return Err(format!("At most {} links are allowed", MAX_LINKS));
+    }
+    normalize_handle(&input.handle)
+}
+
+fn handle_owner(handle: &str) -> Option<Principal> {
+    HANDLES.with(|h| h.borrow().get(handle).copied())
+}
+
+/// Look up a profile by principal (used to resolve artwork author names)
+pub(crate) fn profile_of(user: &Principal) -> Option<UserProfile> {
+    PROFILES.with(|p| p.borrow().get(user).cloned())
+}
+
+/// Store a contact email for a user unless they already have one
+pub(crate) fn set_contact_if_missing(user: Principal, email: String) {
+    CONTACTS.with(|c| {
+        c.borrow_mut().entry(user).or_insert(email);
+    });
+}
+
+/// Create the caller's profile. Fails if one already exists or the handle is taken.
+#[update]
+pub fn register_profile(input: ProfileInput) -> Result<UserProfile, String> {
+    let user = caller();
+    if user == Principal::anonymous() {
+        return Err("Anonymous users cannot register a profile".to_string());
+    }
+    if profile_of(&user).is_some() {
+        return Err("Profile already exists, use update_profile".to_string());
+    }
+
+    let handle = validate_input(&input)?;
+    if handle_owner(&handle).is_some() {
+        return Err(format!("Handle '{}' is already taken", handle));
+    }
+
+    let now = time();
+    let profile = UserProfile {
+        principal: user,
+        display_name: input.display_name.trim().to_string(),
+        handle: handle.clone(),
+        bio: input.bio,
+        avatar_cid: input.avatar_cid,
+        links: input.links,
+        preferred_media_types: input.preferred_media_types,
+        created_at: now,
+        updated_at: now,
+    };
+
+    HANDLES.with(|h| h.borrow_mut().insert(handle, user));
+    PROFILES.with(|p| p.borrow_mut().insert(user, profile.clone()));
+    Ok(profile)
+}
+
+/// Replace the caller's profile fields. Changing the handle releases the old one.
+#[update]
+pub fn update_profile(input: ProfileInput) -> Result<UserProfile, String> {
+    let user = caller();
+    let existing = profile_of(&user).ok_or_else(|| "Profile not found, use register_profile".to_string())?;
+
+    let handle = validate_input(&input)?;
+    if let Some(owner) = handle_owner(&handle) {
+        if owner != user {
+            return Err(format!("Handle '{}' is already taken", handle));
+        }
+    }
+
+    let profile = UserProfile {
+        principal: user,
+        display_name: input.display_name.trim().to_string(),
+        handle: handle.clone(),
+        bio: input.bio,
+        avatar_cid: input.avatar_cid,
+        links: input.links,
+        preferred_media_types: input.preferred_media_types,
+        created_at: existing.created_at,
+        updated_at: time(),
+    };
+
+    HANDLES.with(|h| {
+        let mut handles = h.borrow_mut();
+        handles.remove(&existing.handle);
+        handles.insert(handle, user);
+    });
+    PROFILES.with(|p| p.borrow_mut().insert(user, profile.clone()));
+    Ok(profile)
+}
+
+#[query]
+pub fn get_profile(user: Principal) -> Option<UserProfile> {
+    profile_of(&user)
+}
+
+#[query]
+pub fn get_profile_by_handle(handle: String) -> Option<UserProfile> {
+    let owner = handle_owner(&handle.trim().to_lowercase())?;
+    profile_of(&owner)
+}
+
+/// Set the caller's private contact email (pass an empty string to remove it)
+#[update]
+pub fn set_contact_email(email: String) -> Result<String, String> {
+    let user = caller();
+    if user == Principal::anonymous() {
+        return Err("Anonymous users cannot set a contact email".to_string());
+    }
+
+    let email = email.trim().to_string();
+    if email.is_empty() {
+        CONTACTS.with(|c| c.borrow_mut().remove(&user));
+        return Ok("Contact email removed".to_string());
+    }
+    if !email.contains('@') {
+        return Err("Invalid email address".to_string());
+    }
+
+    CONTACTS.with(|c| c.borrow_mut().insert(user, email));
+    Ok("Contact email updated".to_string())
+}
+
+/// Contact email of a user, visible only to that user and admins
+#[query]
+pub fn get_contact_email(user: Principal) -> Result<Option<String>, String> {
+    let requester = caller();
+    if requester != user && !crate::is_admin(&requester) {
+        return Err("Unauthorized: contact details are private".to_string());
+    }
+    Ok(CONTACTS.with(|c| c.borrow().get(&user).cloned()))
+}
+
+/// One-time migration: move emails stored on existing artworks into the
+/// authors' private contacts and strip them from the artwork records (admin only)
+#[update]
+pub fn migrate_artwork_emails() -> Result<String, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+
+    let mut stripped = 0;
+    crate::ARTWORKS.with(|arts| {
+        for art in arts.borrow_mut().iter_mut().filter(|a| !a.email.is_empty()) {
+            let email = std::mem::take(&mut art.email);
+            if !email.trim().is_empty() {
+                set_contact_if_missing(art.author, email.trim().to_string());
+            }
+            stripped += 1;
+        }
+    });
+
+    Ok(format!("Stripped emails from {} artworks", stripped))
+}
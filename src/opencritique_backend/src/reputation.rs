@@ -0,0 +1,363 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use ic_cdk_timers::TimerId;
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Admin-controlled settings for the seasonal reputation model
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SeasonConfig {
+    /// When false only lifetime points are tracked
+    pub enabled: bool,
+    /// Season length used when `start_season` is called without a duration
+    pub default_duration_ns: Option<u64>,
+    /// Start the next season automatically when a timed season ends
+    pub auto_rollover: bool,
+    /// Share of an inactive user's current-season score removed per decay run (basis points)
+    pub decay_bps: u16,
+    /// A user counts as inactive after this long without any activity
+    pub inactivity_ns: u64,
+    /// How often the decay job runs (0 disables decay)
+    pub decay_interval_ns: u64,
+}
+
+impl Default for SeasonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_duration_ns: None,
+            auto_rollover: false,
+            decay_bps: 0,
+            inactivity_ns: 14 * DAY_NS,
+            decay_interval_ns: DAY_NS,
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Season {
+    pub id: u64,
+    pub name: String,
+    pub started_at: u64,
+    pub ends_at: Option<u64>, // None = closed manually by an admin
+}
+
+/// Archived final standings of a closed season
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SeasonSnapshot {
+    pub season: Season,
+    pub closed_at: u64,
+    pub scores: Vec<(Principal, u64)>, // sorted by score, highest first
+}
+
+/// Critic level by lifetime reputation, used to restrict who a critique request pays
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CriticTier {
+    Newcomer,    // anyone
+    Established, // 25+ points
+    Expert,      // 100+ points
+}
+
+impl CriticTier {
+    fn min_points(self) -> u64 {
+        match self {
+            CriticTier::Newcomer => 0,
+            CriticTier::Established => 25,
+            CriticTier::Expert => 100,
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Reputation {
+    pub lifetime: u64,
+    pub current_season: u64,
+    pub season_id: Option<u64>,
+    pub last_active: Option<u64>,
+}
+
+thread_local! {
+    static SEASON_CONFIG: RefCell<SeasonConfig> = RefCell::new(SeasonConfig::default());
+    static CURRENT_SEASON: RefCell<Option<Season>> = const { RefCell::new(None) };
+    static SEASON_POINTS: RefCell<HashMap<Principal, u64>> = RefCell::new(HashMap::new());
+    static LAST_ACTIVE: RefCell<HashMap<Principal, u64>> = RefCell::new(HashMap::new());
+    static SEASON_HISTORY: RefCell<Vec<SeasonSnapshot>> = const { RefCell::new(Vec::new()) };
+    static SEASON_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+    static DECAY_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+}
+
+/// Upgrade snapshot of season settings, scores and history
+#[derive(CandidType, Deserialize)]
+pub(crate) struct ReputationState {
+    season_config: SeasonConfig,
+    current_season: Option<Season>,
+    season_points: HashMap<Principal, u64>,
+    last_active: HashMap<Principal, u64>,
+    season_history: Vec<SeasonSnapshot>,
+}
+
+pub(crate) fn save_state() -> ReputationState {
+    ReputationState {
+        season_config: SEASON_CONFIG.with(|s| s.take()),
+        current_season: CURRENT_SEASON.with(|s| s.take()),
+        season_points: SEASON_POINTS.with(|s| s.take()),
+        last_active: LAST_ACTIVE.with(|s| s.take()),
+        season_history: SEASON_HISTORY.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: ReputationState) {
+    SEASON_CONFIG.with(|s| *s.borrow_mut() = state.season_config);
+    CURRENT_SEASON.with(|s| *s.borrow_mut() = state.current_season);
+    SEASON_POINTS.with(|s| *s.borrow_mut() = state.season_points);
+    LAST_ACTIVE.with(|s| *s.borrow_mut() = state.last_active);
+    SEASON_HISTORY.with(|s| *s.borrow_mut() = state.season_history);
+}
+
+fn require_admin() -> Result<(), String> {
+    if crate::is_admin(&caller()) {
+        Ok(())
+    } else {
+        Err("Unauthorized: admin only".to_string())
+    }
+}
+
+/// Record activity for a user so they are not treated as inactive by decay
+pub(crate) fn touch_activity(user: Principal) {
+    LAST_ACTIVE.with(|active| {
+        active.borrow_mut().insert(user, time());
+    });
+}
+
+/// Award reputation points: always to the lifetime score, and to the
+/// current-season score while a season is open
+pub(crate) fn award_points(user: Principal, amount: u64) {
+    crate::POINTS.with(|pts| {
+        *pts.borrow_mut().entry(user).or_insert(0) += amount;
+    });
+
+    let season_open = SEASON_CONFIG.with(|c| c.borrow().enabled)
+        && CURRENT_SEASON.with(|s| s.borrow().is_some());
+    if season_open {
+        SEASON_POINTS.with(|pts| {
+            *pts.borrow_mut().entry(user).or_insert(0) += amount;
+        });
+    }
+
+    touch_activity(user);
+}
+
+/// Users whose lifetime score is at least `min_points`
+pub(crate) fn users_with_points(min_points: u64) -> Vec<Principal> {
+    crate::POINTS.with(|pts| {
+        pts.borrow()
+            .iter()
+            .filter(|(_, points)| **points >= min_points)
+            .map(|(user, _)| *user)
+            .collect()
+    })
+}
+
+/// Highest tier a user's lifetime score qualifies for
+pub(crate) fn critic_tier(user: Principal) -> CriticTier {
+    let points = crate::POINTS.with(|pts| *pts.borrow().get(&user).unwrap_or(&0));
+    [CriticTier::Expert, CriticTier::Established]
+        .into_iter()
+        .find(|tier| points >= tier.min_points())
+        .unwrap_or(CriticTier::Newcomer)
+}
+
+fn arm_season_timer(ends_at: u64) {
+    let delay = Duration::from_nanos(ends_at.saturating_sub(time()));
+    let timer_id = ic_cdk_timers::set_timer(delay, on_season_timer);
+    if let Some(old) = SEASON_TIMER.with(|t| t.borrow_mut().replace(timer_id)) {
+        ic_cdk_timers::clear_timer(old);
+    }
+}
+
+fn clear_season_timer() {
+    if let Some(old) = SEASON_TIMER.with(|t| t.borrow_mut().take()) {
+        ic_cdk_timers::clear_timer(old);
+    }
+}
+
+fn arm_decay_timer(config: &SeasonConfig) {
+    if let Some(old) = DECAY_TIMER.with(|t| t.borrow_mut().take()) {
+        ic_cdk_timers::clear_timer(old);
+    }
+    if !config.enabled || config.decay_bps == 0 || config.decay_interval_ns == 0 {
+        return;
+    }
+    let timer_id = ic_cdk_timers::set_timer_interval(
+        Duration::from_nanos(config.decay_interval_ns),
+        apply_decay,
+    );
+    DECAY_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+}
+
+fn open_season(name: String, duration_ns: Option<u64>) -> Result<Season, String> {
+    let id = SEASON_HISTORY.with(|h| h.borrow().len() as u64) + 1;
+    let started_at = time();
+    let ends_at = match duration_ns {
+        Some(d) => Some(started_at.checked_add(d).ok_or_else(|| "Season duration is too long".to_string())?),
+        None => None,
+    };
+    let season = Season {
+        id,
+        name,
+        started_at,
+        ends_at,
+    };
+
+    SEASON_POINTS.with(|pts| pts.borrow_mut().clear());
+    CURRENT_SEASON.with(|s| *s.borrow_mut() = Some(season.clone()));
+    if let Some(ends_at) = season.ends_at {
+        arm_season_timer(ends_at);
+    }
+    Ok(season)
+}
+
+fn close_current_season() -> Option<SeasonSnapshot> {
+    let season = CURRENT_SEASON.with(|s| s.borrow_mut().take())?;
+    clear_season_timer();
+
+    let mut scores: Vec<(Principal, u64)> = SEASON_POINTS.with(|pts| pts.borrow_mut().drain().collect());
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+
+    let snapshot = SeasonSnapshot {
+        season,
+        closed_at: time(),
+        scores,
+    };
+    SEASON_HISTORY.with(|h| h.borrow_mut().push(snapshot.clone()));
+    Some(snapshot)
+}
+
+fn on_season_timer() {
+    SEASON_TIMER.with(|t| *t.borrow_mut() = None);
+    let Some(snapshot) = close_current_season() else { return };
+
+    let config = SEASON_CONFIG.with(|c| c.borrow().clone());
+    if config.enabled && config.auto_rollover {
+        let duration = snapshot.season.ends_at
+            .map(|end| end.saturating_sub(snapshot.season.started_at))
+            .or(config.default_duration_ns);
+        if let Err(e) = open_season(format!("Season {}", snapshot.season.id + 1), duration) {
+            ic_cdk::println!("Next season not opened: {}", e);
+        }
+    }
+}
+
+/// Reduce the current-season score of every user inactive for longer than
+/// `inactivity_ns`. Lifetime points are never decayed.
+fn apply_decay() {
+    let config = SEASON_CONFIG.with(|c| c.borrow().clone());
+    if !config.enabled || config.decay_bps == 0 {
+        return;
+    }
+    let cutoff = time().saturating_sub(config.inactivity_ns);
+
+    SEASON_POINTS.with(|pts| {
+        let mut pts = pts.borrow_mut();
+        LAST_ACTIVE.with(|active| {
+            let active = active.borrow();
+            for (user, score) in pts.iter_mut() {
+                let last = active.get(user).copied().unwrap_or(0);
+                if last < cutoff {
+                    *score -= *score * config.decay_bps.min(10_000) as u64 / 10_000;
+                }
+            }
+        });
+        pts.retain(|_, score| *score > 0);
+    });
+}
+
+/// Re-arm the season end and decay timers (timers do not survive upgrades)
+pub(crate) fn rearm_timers() {
+    let config = SEASON_CONFIG.with(|c| c.borrow().clone());
+    arm_decay_timer(&config);
+    if let Some(ends_at) = CURRENT_SEASON.with(|s| s.borrow().as_ref().and_then(|season| season.ends_at)) {
+        arm_season_timer(ends_at);
+    }
+}
+
+/// Update the seasonal reputation settings (admin only)
+#[update]
+pub fn configure_seasons(config: SeasonConfig) -> Result<String, String> {
+    require_admin()?;
+    if config.decay_bps > 10_000 {
+        return Err("decay_bps cannot exceed 10000".to_string());
+    }
+
+    arm_decay_timer(&config);
+    SEASON_CONFIG.with(|c| *c.borrow_mut() = config);
+    Ok("Season configuration updated".to_string())
+}
+
+#[query]
+pub fn get_season_config() -> SeasonConfig {
+    SEASON_CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Open a new season (admin only). Falls back to the configured default duration.
+#[update]
+pub fn start_season(name: String, duration_ns: Option<u64>) -> Result<Season, String> {
+    require_admin()?;
+
+    let config = SEASON_CONFIG.with(|c| c.borrow().clone());
+    if !config.enabled {
+        return Err("Seasons are disabled".to_string());
+    }
+    if CURRENT_SEASON.with(|s| s.borrow().is_some()) {
+        return Err("A season is already open".to_string());
+    }
+    if duration_ns == Some(0) {
+        return Err("Season duration must be greater than zero".to_string());
+    }
+
+    open_season(name, duration_ns.or(config.default_duration_ns))
+}
+
+/// Close the current season and archive its standings (admin only)
+#[update]
+pub fn close_season() -> Result<SeasonSnapshot, String> {
+    require_admin()?;
+    close_current_season().ok_or_else(|| "No season is open".to_string())
+}
+
+#[query]
+pub fn get_current_season() -> Option<Season> {
+    CURRENT_SEASON.with(|s| s.borrow().clone())
+}
+
+#[query]
+pub fn get_season_history() -> Vec<SeasonSnapshot> {
+    SEASON_HISTORY.with(|h| h.borrow().clone())
+}
+
+/// Lifetime and current-season reputation for a user
+#[query]
+pub fn get_reputation(user: Principal) -> Reputation {
+    Reputation {
+        lifetime: crate::POINTS.with(|pts| *pts.borrow().get(&user).unwrap_or(&0)),
+        current_season: SEASON_POINTS.with(|pts| *pts.borrow().get(&user).unwrap_or(&0)),
+        season_id: CURRENT_SEASON.with(|s| s.borrow().as_ref().map(|season| season.id)),
+        last_active: LAST_ACTIVE.with(|active| active.borrow().get(&user).copied()),
+    }
+}
+
+/// Top users of the current season, highest score first
+#[query]
+pub fn get_season_leaderboard(limit: u64) -> Vec<(Principal, u64)> {
+    let mut scores: Vec<(Principal, u64)> = SEASON_POINTS.with(|pts| {
+        pts.borrow().iter().map(|(p, s)| (*p, *s)).collect()
+    });
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    scores.truncate(limit as usize);
+    scores
+}
@@ -0,0 +1,59 @@
+// System API used by the canister modules. The mock-ledger tests run natively,
+// where the ic0 calls are not available, so they get a caller, clock and
+// canister id they can set instead.
+
+#[cfg(not(all(test, feature = "mock-ledger")))]
+pub(crate) use ic_cdk::api::{caller, is_controller, time};
+#[cfg(not(all(test, feature = "mock-ledger")))]
+pub(crate) use ic_cdk::id;
+
+#[cfg(all(test, feature = "mock-ledger"))]
+pub(crate) use native::*;
+
+#[cfg(all(test, feature = "mock-ledger"))]
+mod native {
+    use candid::Principal;
+    use std::cell::Cell;
+
+    const CANISTER: Principal = Principal::from_slice(&[1; 10]);
+
+    thread_local! {
+        static CALLER: Cell<Principal> = const { Cell::new(Principal::anonymous()) };
+        static NOW: Cell<u64> = const { Cell::new(1_700_000_000_000_000_000) };
+    }
+
+    pub(crate) fn caller() -> Principal {
+        CALLER.with(|c| c.get())
+    }
+
+    pub(crate) fn time() -> u64 {
+        NOW.with(|n| n.get())
+    }
+
+    pub(crate) fn id() -> Principal {
+        CANISTER
+    }
+
+    pub(crate) fn is_controller(_principal: &Principal) -> bool {
+        false
+    }
+
+    pub(crate) fn set_caller(principal: Principal) {
+        CALLER.with(|c| c.set(principal));
+    }
+
+    pub(crate) fn advance_time(ns: u64) {
+        NOW.with(|n| n.set(n.get() + ns));
+    }
+
+    /// Run an update call to completion. The mock ledger answers at once, so
+    /// an endpoint that is still pending after one poll is stuck.
+    pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(output) => output,
+            std::task::Poll::Pending => panic!("update call did not complete"),
+        }
+    }
+}
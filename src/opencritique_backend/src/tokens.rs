@@ -0,0 +1,199 @@
+use crate::runtime::{caller, time};
+use ic_cdk::api::call::CallResult;
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Nat, Principal};
+use icrc_ledger_types::icrc::generic_metadata_value::MetadataValue;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::config::{ledger_canister_id, ledger_fee};
+
+// Amounts are kept in u64 of the smallest unit, which must hold at least a
+// few whole tokens (u64::MAX is about 18.4 * 10^18)
+const MAX_DECIMALS: u8 = 18;
+
+/// An ICRC-1 ledger that bounties can be denominated in
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TokenInfo {
+    pub ledger: Principal,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    pub fee: u64, // transfer fee in the token's smallest unit
+    /// Disabled tokens cannot be chosen for new bounties; existing ones keep working
+    pub enabled: bool,
+    pub updated_at: u64,
+}
+
+thread_local! {
+    // Entries are never removed, so every bounty can always resolve its token
+    static TOKENS: RefCell<BTreeMap<Principal, TokenInfo>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Upgrade snapshot of the token registry
+#[derive(CandidType, Deserialize)]
+pub(crate) struct TokenState {
+    tokens: BTreeMap<Principal, TokenInfo>,
+}
+
+pub(crate) fn save_state() -> TokenState {
+    TokenState {
+        tokens: TOKENS.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: TokenState) {
+    TOKENS.with(|s| *s.borrow_mut() = state.tokens);
+}
+
+// The configured ICP ledger is always supported, with the configured fee,
+// unless an admin registered or disabled it explicitly
+fn icp_token() -> TokenInfo {
+    TokenInfo {
+        ledger: ledger_canister_id(),
+        symbol: "ICP".to_string(),
+        name: "Internet Computer".to_string(),
+        decimals: 8,
+        fee: ledger_fee(),
+        enabled: true,
+        updated_at: 0,
+    }
+}
+
+pub(crate) fn token_info(ledger: Principal) -> Option<TokenInfo> {
+    TOKENS.with(|t| t.borrow().get(&ledger).cloned())
+        .or_else(|| (ledger == ledger_canister_id()).then(icp_token))
+}
+
+/// Registry entry of a ledger, or a placeholder for a ledger that is not registered
+pub(crate) fn token_or_placeholder(ledger: Principal) -> TokenInfo {
+    token_info(ledger).unwrap_or_else(|| TokenInfo {
+        ledger,
+        symbol: "?".to_string(),
+        name: "Unregistered token".to_string(),
+        decimals: 8,
+        fee: ledger_fee(),
+        enabled: false,
+        updated_at: 0,
+    })
+}
+
+/// Transfer fee of a ledger, falling back to the ICP fee for unknown ledgers
+pub(crate) fn token_fee(ledger: Principal) -> u64 {
+    token_info(ledger).map(|t| t.fee).unwrap_or_else(ledger_fee)
+}
+
+/// Human-readable amount, e.g. "1.5 ckBTC"
+pub(crate) fn format_amount(ledger: Principal, amount: u64) -> String {
+    match token_info(ledger) {
+        Some(token) => format!(
+            "{} {}",
+            amount as f64 / 10f64.powi(token.decimals as i32),
+            token.symbol
+        ),
+        None => format!("{} units", amount),
+    }
+}
+
+fn nat_to_u64(n: &Nat) -> Option<u64> {
+    u64::try_from(n.0.clone()).ok()
+}
+
+async fn icrc1_metadata(ledger: Principal) -> CallResult<(Vec<(String, MetadataValue)>,)> {
+    #[cfg(feature = "mock-ledger")]
+    if crate::config::is_testing() {
+        return crate::mock_ledger::icrc1_metadata(ledger);
+    }
+    ic_cdk::call(ledger, "icrc1_metadata", ()).await
+}
+
+async fn icrc1_fee(ledger: Principal) -> CallResult<(Nat,)> {
+    #[cfg(feature = "mock-ledger")]
+    if crate::config::is_testing() {
+        return crate::mock_ledger::icrc1_fee(ledger);
+    }
+    ic_cdk::call(ledger, "icrc1_fee", ()).await
+}
+
+// Read symbol, name, decimals and fee from the ledger itself
+async fn fetch_token_info(ledger: Principal) -> Result<TokenInfo, String> {
+    let (metadata,) = icrc1_metadata(ledger)
+        .await
+        .map_err(|(code, msg)| format!("icrc1_metadata failed: {}: {}", code as u8, msg))?;
+    let (fee,) = icrc1_fee(ledger)
+        .await
+        .map_err(|(code, msg)| format!("icrc1_fee failed: {}: {}", code as u8, msg))?;
+
+    let text = |key: &str| {
+        metadata.iter().find_map(|(k, v)| match v {
+            MetadataValue::Text(s) if k == key => Some(s.clone()),
+            _ => None,
+        })
+    };
+    let decimals = metadata.iter().find_map(|(k, v)| match v {
+        MetadataValue::Nat(n) if k == "icrc1:decimals" => nat_to_u64(n),
+        _ => None,
+    });
+
+    let symbol = text("icrc1:symbol").ok_or("Ledger metadata has no icrc1:symbol")?;
+    let decimals = decimals
+        .and_then(|d| u8::try_from(d).ok())
+        .ok_or("Ledger metadata has no valid icrc1:decimals")?;
+    if decimals > MAX_DECIMALS {
+        return Err(format!("Tokens with more than {} decimals are not supported", MAX_DECIMALS));
+    }
+    let fee = nat_to_u64(&fee).ok_or("Ledger fee exceeds u64")?;
+
+    Ok(TokenInfo {
+        ledger,
+        name: text("icrc1:name").unwrap_or_else(|| symbol.clone()),
+        symbol,
+        decimals,
+        fee,
+        enabled: true,
+        updated_at: time(),
+    })
+}
+
+/// Add an ICRC-1 ledger to the registry, or refresh its metadata and fee if
+/// already present (admin only)
+#[update]
+pub async fn add_supported_token(ledger: Principal) -> Result<TokenInfo, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+
+    let token = fetch_token_info(ledger).await?;
+    TOKENS.with(|t| t.borrow_mut().insert(ledger, token.clone()));
+    Ok(token)
+}
+
+/// Enable or disable a token for new bounties (admin only)
+#[update]
+pub fn set_token_enabled(ledger: Principal, enabled: bool) -> Result<TokenInfo, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+
+    let default = token_info(ledger).ok_or_else(|| "Token not found".to_string())?;
+    TOKENS.with(|t| {
+        let mut tokens = t.borrow_mut();
+        let token = tokens.entry(ledger).or_insert(default);
+        token.enabled = enabled;
+        token.updated_at = time();
+        Ok(token.clone())
+    })
+}
+
+/// Tokens artists can currently choose for a bounty
+#[query]
+pub fn get_supported_tokens() -> Vec<TokenInfo> {
+    let icp = ledger_canister_id();
+    let mut tokens: Vec<TokenInfo> = TOKENS.with(|t| t.borrow().values().cloned().collect());
+    if !tokens.iter().any(|token| token.ledger == icp) {
+        tokens.insert(0, icp_token());
+    }
+    tokens.retain(|token| token.enabled);
+    tokens
+}
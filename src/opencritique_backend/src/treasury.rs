@@ -0,0 +1,721 @@
+use crate::runtime::{caller, time};
+use ic_cdk::{update, query};
+use serde::{Deserialize, Serialize};
+use candid::{CandidType, Principal};
+use icrc_ledger_types::icrc1::transfer::Memo;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bounty::{
+    escrow_icrc_account, ledger_balance, ledger_transfer, ledger_transfer_from, treasury_subaccount, Account, BountyKey,
+    TransferFailure,
+};
+use crate::config::{config, ledger_canister_id, platform_fee_bps, MAX_PLATFORM_FEE_BPS};
+use crate::tokens::token_fee;
+
+// Ledgers only deduplicate transactions created within this window. A retry
+// of a transfer with unknown outcome that is older could pay twice.
+const LEDGER_DEDUP_WINDOW_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Where treasury funds came from or went to
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize)]
+pub enum TreasurySource {
+    Bounty(BountyKey), // platform fee on a bounty payout
+    NftSale(u64),      // platform fee on the sale of an artwork
+    Withdrawal(u64),   // executed withdrawal proposal
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TreasuryEntry {
+    pub id: u64,
+    pub ledger: Principal,
+    pub source: TreasurySource,
+    pub amount: u64, // received, or sent out for withdrawals (ledger fee not included)
+    pub block_index: Option<u64>,
+    pub timestamp: u64,
+}
+
+/// Payment for an NFT. The price is taken into the treasury and the seller's
+/// share forwarded from there.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct NftSale {
+    pub artwork_id: u64,
+    pub buyer: Principal,
+    pub seller: Principal,
+    pub ledger: Principal,
+    pub price: u64,
+    pub platform_fee: u64,
+    pub seller_amount: u64, // price minus the platform fee and the ledger fee of the payout
+    pub payment_block: u64,
+    pub sold_at: u64,
+    pub payout_created_at: u64, // reused by retries of a transfer with unknown outcome, so the ledger deduplicates them
+    pub payout_block: Option<u64>,
+    pub payout_error: Option<String>,
+    #[serde(default)]
+    pub payout_unknown: bool, // the last payout attempt may have gone through
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum WithdrawalStatus {
+    Open, // waiting for approvals, or for a failed transfer to be retried
+    Executed,
+    Cancelled,
+}
+
+/// A proposal to move funds out of the treasury. It runs once a majority of
+/// the configured admins approved it.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TreasuryWithdrawal {
+    pub id: u64,
+    pub ledger: Principal,
+    pub to: Account,
+    pub amount: u64,
+    pub reason: String,
+    pub proposed_by: Principal,
+    pub proposed_at: u64,
+    pub approvals: Vec<Principal>,
+    pub status: WithdrawalStatus,
+    pub created_at_time: Option<u64>, // set by the first transfer attempt, kept while its outcome is unknown
+    pub executed_at: Option<u64>,
+    pub block_index: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Treasury funds on one ledger
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct TreasuryBalance {
+    pub ledger: Principal,
+    pub account: Account,
+    pub balance: u64,
+    pub owed_to_sellers: u64, // NFT proceeds not forwarded yet, including their ledger fees
+    pub available: u64,       // what withdrawals can take
+}
+
+thread_local! {
+    static HISTORY: RefCell<Vec<TreasuryEntry>> = const { RefCell::new(Vec::new()) };
+    static NFT_SALES: RefCell<BTreeMap<u64, NftSale>> = const { RefCell::new(BTreeMap::new()) };
+    static NFT_SALES_IN_PROGRESS: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+    static WITHDRAWALS: RefCell<Vec<TreasuryWithdrawal>> = const { RefCell::new(Vec::new()) };
+    static WITHDRAWAL_ID: RefCell<u64> = const { RefCell::new(0) };
+    static WITHDRAWALS_EXECUTING: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Upgrade snapshot of the treasury ledger, NFT sales and withdrawals
+#[derive(CandidType, Deserialize)]
+pub(crate) struct TreasuryState {
+    history: Vec<TreasuryEntry>,
+    nft_sales: BTreeMap<u64, NftSale>,
+    withdrawals: Vec<TreasuryWithdrawal>,
+    withdrawal_id: u64,
+}
+
+pub(crate) fn save_state() -> TreasuryState {
+    TreasuryState {
+        history: HISTORY.with(|s| s.take()),
+        nft_sales: NFT_SALES.with(|s| s.take()),
+        withdrawals: WITHDRAWALS.with(|s| s.take()),
+        withdrawal_id: WITHDRAWAL_ID.with(|s| s.take()),
+    }
+}
+
+pub(crate) fn restore_state(state: TreasuryState) {
+    HISTORY.with(|s| *s.borrow_mut() = state.history);
+    NFT_SALES.with(|s| *s.borrow_mut() = state.nft_sales);
+    WITHDRAWALS.with(|s| *s.borrow_mut() = state.withdrawals);
+    WITHDRAWAL_ID.with(|s| *s.borrow_mut() = state.withdrawal_id);
+}
+
+fn fee_of(amount: u64) -> u64 {
+    (amount as u128 * platform_fee_bps() as u128 / 10_000) as u64
+}
+
+/// The platform's share of a bounty payout. Nothing is taken when the share
+/// would not cover the ledger fee of sending it to the treasury.
+pub(crate) fn platform_cut(amount: u64, ledger_fee: u64) -> u64 {
+    let cut = fee_of(amount);
+    if cut > ledger_fee { cut } else { 0 }
+}
+
+pub(crate) fn record_entry(ledger: Principal, source: TreasurySource, amount: u64, block_index: Option<u64>) {
+    HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        let id = history.len() as u64 + 1;
+        history.push(TreasuryEntry { id, ledger, source, amount, block_index, timestamp: time() });
+    });
+}
+
+// Memo of a transfer out of the treasury, distinct per purpose and id
+fn treasury_memo(purpose: &[u8], id: u64) -> Memo {
+    let mut memo = purpose.to_vec();
+    memo.extend_from_slice(&id.to_be_bytes());
+    Memo(serde_bytes::ByteBuf::from(memo))
+}
+
+fn treasury_account() -> Account {
+    escrow_icrc_account(treasury_subaccount())
+}
+
+fn owed_to_sellers(ledger: Principal) -> u64 {
+    NFT_SALES.with(|s| {
+        s.borrow()
+            .values()
+            .filter(|sale| sale.ledger == ledger && sale.payout_block.is_none() && sale.seller_amount > 0)
+            .map(|sale| sale.seller_amount + token_fee(ledger))
+            .sum()
+    })
+}
+
+/// Held while an NFT is being paid for, so two buyers cannot both pay
+pub(crate) struct NftSaleLock(u64);
+
+impl NftSaleLock {
+    pub(crate) fn acquire(artwork_id: u64) -> Result<Self, String> {
+        if NFT_SALES_IN_PROGRESS.with(|s| s.borrow_mut().insert(artwork_id)) {
+            Ok(NftSaleLock(artwork_id))
+        } else {
+            Err("A purchase of this NFT is already in progress".to_string())
+        }
+    }
+}
+
+impl Drop for NftSaleLock {
+    fn drop(&mut self) {
+        NFT_SALES_IN_PROGRESS.with(|s| s.borrow_mut().remove(&self.0));
+    }
+}
+
+/// Take the price of an NFT from the buyer's allowance (granted with
+/// `icrc2_approve` on the ICP ledger) into the treasury, keep the platform fee
+/// and forward the rest to the seller. A failed seller payout does not undo
+/// the sale; it is kept on the sale record for `retry_nft_sale_payout`.
+/// Callers must hold the sale lock. Free NFTs need no payment.
+pub(crate) async fn collect_nft_payment(artwork_id: u64, buyer: Principal, seller: Principal, price: u64) -> Result<Option<NftSale>, String> {
+    if price == 0 {
+        return Ok(None);
+    }
+    let ledger = ledger_canister_id();
+    let payment_block = ledger_transfer_from(ledger, treasury_memo(b"nft-pay", artwork_id), buyer, treasury_subaccount(), price)
+        .await
+        .map_err(|e| format!("Payment failed: {:?}", e))?;
+
+    let platform_fee = fee_of(price);
+    let now = time();
+    let sale = NftSale {
+        artwork_id,
+        buyer,
+        seller,
+        ledger,
+        price,
+        platform_fee,
+        seller_amount: price.saturating_sub(platform_fee + token_fee(ledger)),
+        payment_block,
+        sold_at: now,
+        payout_created_at: now,
+        payout_block: None,
+        payout_error: None,
+        payout_unknown: false,
+    };
+    NFT_SALES.with(|s| s.borrow_mut().insert(artwork_id, sale));
+    if platform_fee > 0 {
+        record_entry(ledger, TreasurySource::NftSale(artwork_id), platform_fee, Some(payment_block));
+    }
+
+    pay_seller(artwork_id).await;
+    Ok(get_nft_sale(artwork_id))
+}
+
+// Forward the seller's share of a sale from the treasury. A payout whose
+// outcome is unknown is only retried while the ledger still deduplicates it;
+// after that an admin resolves it with `resolve_nft_sale_payout`.
+async fn pay_seller(artwork_id: u64) {
+    let Some(sale) = get_nft_sale(artwork_id) else { return };
+    if sale.payout_block.is_some() || sale.seller_amount == 0 {
+        return;
+    }
+    let created_at_time = if sale.payout_unknown {
+        if time() > sale.payout_created_at.saturating_add(LEDGER_DEDUP_WINDOW_NS) {
+            let error = "The last payout attempt is outside the ledger deduplication window; an admin must check the ledger and resolve it".to_string();
+            with_sale_mut(artwork_id, |sale| sale.payout_error = Some(error));
+            return;
+        }
+        sale.payout_created_at
+    } else {
+        // Nothing was sent yet, so this is a new transfer
+        let now = time();
+        with_sale_mut(artwork_id, |sale| sale.payout_created_at = now);
+        now
+    };
+
+    let result = ledger_transfer(
+        sale.ledger,
+        treasury_subaccount(),
+        Account { owner: sale.seller, subaccount: None },
+        sale.seller_amount,
+        token_fee(sale.ledger),
+        treasury_memo(b"nft", artwork_id),
+        created_at_time,
+    ).await;
+
+    with_sale_mut(artwork_id, |sale| match result {
+        Ok(block_index) => {
+            sale.payout_block = Some(block_index);
+            sale.payout_error = None;
+            sale.payout_unknown = false;
+        }
+        Err(TransferFailure::Rejected(e)) => {
+            sale.payout_error = Some(format!("{:?}", e));
+            sale.payout_unknown = false;
+        }
+        Err(TransferFailure::Unknown(msg)) => {
+            sale.payout_error = Some(msg);
+            sale.payout_unknown = true;
+        }
+    });
+}
+
+fn with_sale_mut<R>(artwork_id: u64, f: impl FnOnce(&mut NftSale) -> R) -> Option<R> {
+    NFT_SALES.with(|s| s.borrow_mut().get_mut(&artwork_id).map(f))
+}
+
+/// Payment record of a sold NFT
+#[query]
+pub fn get_nft_sale(artwork_id: u64) -> Option<NftSale> {
+    NFT_SALES.with(|s| s.borrow().get(&artwork_id).cloned())
+}
+
+/// Retry forwarding the proceeds of an NFT sale whose payout failed (seller or admin)
+#[update]
+pub async fn retry_nft_sale_payout(artwork_id: u64) -> Result<NftSale, String> {
+    let sale = get_nft_sale(artwork_id).ok_or("No sale recorded for this artwork")?;
+    let requester = caller();
+    if requester != sale.seller && !crate::is_admin(&requester) {
+        return Err("Unauthorized: only the seller or an admin can retry the payout".to_string());
+    }
+    let _lock = NftSaleLock::acquire(artwork_id)?;
+    pay_seller(artwork_id).await;
+    get_nft_sale(artwork_id).ok_or_else(|| "No sale recorded for this artwork".to_string())
+}
+
+/// Resolve an NFT sale payout with unknown outcome by hand after checking the
+/// ledger (admin only): pass the block index if it was executed, or None to
+/// send it again as a new transfer on the next retry.
+#[update]
+pub fn resolve_nft_sale_payout(artwork_id: u64, block_index: Option<u64>) -> Result<NftSale, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    let _lock = NftSaleLock::acquire(artwork_id)?;
+    with_sale_mut(artwork_id, |sale| {
+        if sale.payout_block.is_some() {
+            return Err("The seller was already paid".to_string());
+        }
+        if !sale.payout_unknown {
+            return Err("The payout has no attempt with unknown outcome".to_string());
+        }
+        sale.payout_unknown = false;
+        match block_index {
+            Some(block_index) => {
+                sale.payout_block = Some(block_index);
+                sale.payout_error = None;
+            }
+            None => sale.payout_error = Some("Not executed according to an admin; retry the payout".to_string()),
+        }
+        Ok(sale.clone())
+    }).ok_or("No sale recorded for this artwork")?
+}
+
+/// Set the platform fee on bounty payouts and NFT sales, in basis points (admin only)
+#[update]
+pub fn set_platform_fee(bps: u16) -> Result<String, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if bps > MAX_PLATFORM_FEE_BPS {
+        return Err(format!("The platform fee cannot exceed {} basis points", MAX_PLATFORM_FEE_BPS));
+    }
+    crate::config::set_platform_fee_bps(bps);
+    Ok(format!("Platform fee set to {} basis points", bps))
+}
+
+/// Treasury balance on `ledger` (ICP if omitted), read from the ledger (admin only)
+#[update]
+pub async fn get_treasury_balance(ledger: Option<Principal>) -> Result<TreasuryBalance, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    let ledger = ledger.unwrap_or_else(ledger_canister_id);
+    let account = treasury_account();
+    let balance = ledger_balance(ledger, account).await.map_err(|e| format!("{:?}", e))?;
+    let owed_to_sellers = owed_to_sellers(ledger);
+    Ok(TreasuryBalance {
+        ledger,
+        account,
+        balance,
+        owed_to_sellers,
+        available: balance.saturating_sub(owed_to_sellers),
+    })
+}
+
+/// Fees received and withdrawals made, newest first (admin only)
+#[query]
+pub fn get_treasury_history() -> Result<Vec<TreasuryEntry>, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    Ok(HISTORY.with(|h| h.borrow().iter().rev().cloned().collect()))
+}
+
+// A majority of the configured admins; a single approval when none are configured
+fn approvals_needed() -> usize {
+    config().admins.len() / 2 + 1
+}
+
+// Configured admins approve withdrawals. Controllers that are not configured
+// admins only do so while no admins are configured.
+fn can_approve(principal: &Principal) -> bool {
+    let admins = config().admins;
+    if admins.is_empty() {
+        crate::is_admin(principal)
+    } else {
+        admins.contains(principal)
+    }
+}
+
+// Approvals that still count, leaving out those of removed admins
+fn valid_approvals(withdrawal: &TreasuryWithdrawal) -> usize {
+    withdrawal.approvals.iter().filter(|p| can_approve(p)).count()
+}
+
+fn with_withdrawal_mut<R>(id: u64, f: impl FnOnce(&mut TreasuryWithdrawal) -> R) -> Option<R> {
+    WITHDRAWALS.with(|w| w.borrow_mut().iter_mut().find(|w| w.id == id).map(f))
+}
+
+fn get_withdrawal(id: u64) -> Result<TreasuryWithdrawal, String> {
+    WITHDRAWALS.with(|w| w.borrow().iter().find(|w| w.id == id).cloned())
+        .ok_or_else(|| "Withdrawal not found".to_string())
+}
+
+// Clears the executing flag of a withdrawal when its attempt ends
+struct ExecutionGuard(u64);
+
+impl Drop for ExecutionGuard {
+    fn drop(&mut self) {
+        WITHDRAWALS_EXECUTING.with(|e| e.borrow_mut().remove(&self.0));
+    }
+}
+
+// Run a withdrawal that has enough approvals. The balance is checked before
+// the first attempt; retries of a transfer with unknown outcome reuse its
+// created_at_time, so the ledger reports a duplicate instead of paying twice.
+// Once the ledger no longer deduplicates it, `resolve_treasury_withdrawal` must
+// settle it instead.
+async fn execute_if_approved(id: u64) -> Result<TreasuryWithdrawal, String> {
+    let withdrawal = get_withdrawal(id)?;
+    if withdrawal.status != WithdrawalStatus::Open || valid_approvals(&withdrawal) < approvals_needed() {
+        return Ok(withdrawal);
+    }
+    if !WITHDRAWALS_EXECUTING.with(|e| e.borrow_mut().insert(id)) {
+        return Err("This withdrawal is already being executed".to_string());
+    }
+    let _guard = ExecutionGuard(id);
+
+    let fee = token_fee(withdrawal.ledger);
+    let created_at_time = match withdrawal.created_at_time {
+        Some(created_at_time) if time() > created_at_time.saturating_add(LEDGER_DEDUP_WINDOW_NS) => {
+            let error = "The last transfer attempt is outside the ledger deduplication window; an admin must check the ledger and resolve it".to_string();
+            with_withdrawal_mut(id, |w| w.last_error = Some(error.clone()));
+            return Err(error);
+        }
+        Some(created_at_time) => created_at_time,
+        None => {
+            // Funds owed to NFT sellers are not the platform's
+            let balance = ledger_balance(withdrawal.ledger, treasury_account()).await.map_err(|e| format!("{:?}", e))?;
+            if balance.saturating_sub(owed_to_sellers(withdrawal.ledger)) < withdrawal.amount.saturating_add(fee) {
+                let error = "Insufficient treasury funds".to_string();
+                with_withdrawal_mut(id, |w| w.last_error = Some(error.clone()));
+                return Err(error);
+            }
+            let now = time();
+            with_withdrawal_mut(id, |w| w.created_at_time = Some(now));
+            now
+        }
+    };
+
+    let result = ledger_transfer(
+        withdrawal.ledger,
+        treasury_subaccount(),
+        withdrawal.to,
+        withdrawal.amount,
+        fee,
+        treasury_memo(b"withdrawal", id),
+        created_at_time,
+    ).await;
+
+    match result {
+        Ok(block_index) => {
+            with_withdrawal_mut(id, |w| {
+                w.status = WithdrawalStatus::Executed;
+                w.executed_at = Some(time());
+                w.block_index = Some(block_index);
+                w.last_error = None;
+            });
+            record_entry(withdrawal.ledger, TreasurySource::Withdrawal(id), withdrawal.amount, Some(block_index));
+        }
+        // Not executed: the next attempt checks the balance again
+        Err(TransferFailure::Rejected(e)) => {
+            with_withdrawal_mut(id, |w| {
+                w.created_at_time = None;
+                w.last_error = Some(format!("{:?}", e));
+            });
+        }
+        Err(TransferFailure::Unknown(msg)) => {
+            with_withdrawal_mut(id, |w| w.last_error = Some(msg));
+        }
+    }
+    get_withdrawal(id)
+}
+
+/// Propose sending `amount` from the treasury to `to` (admin only). The
+/// proposer's approval counts if they are a configured admin; the transfer
+/// runs once a majority of the configured admins approved it.
+#[update]
+pub async fn propose_treasury_withdrawal(ledger: Principal, to: Account, amount: u64, reason: String) -> Result<TreasuryWithdrawal, String> {
+    let proposer = caller();
+    if !crate::is_admin(&proposer) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if amount == 0 {
+        return Err("Amount must be greater than zero".to_string());
+    }
+    if reason.trim().is_empty() {
+        return Err("A reason is required".to_string());
+    }
+
+    let id = WITHDRAWAL_ID.with(|n| {
+        let mut n = n.borrow_mut();
+        *n += 1;
+        *n
+    });
+    WITHDRAWALS.with(|w| w.borrow_mut().push(TreasuryWithdrawal {
+        id,
+        ledger,
+        to,
+        amount,
+        reason,
+        proposed_by: proposer,
+        proposed_at: time(),
+        approvals: if can_approve(&proposer) { vec![proposer] } else { vec![] },
+        status: WithdrawalStatus::Open,
+        created_at_time: None,
+        executed_at: None,
+        block_index: None,
+        last_error: None,
+    }));
+    execute_if_approved(id).await
+}
+
+/// Approve an open withdrawal (configured admins only, or any admin while
+/// none are configured). Calling it on a withdrawal that already has enough
+/// approvals retries a failed transfer.
+#[update]
+pub async fn approve_treasury_withdrawal(id: u64) -> Result<TreasuryWithdrawal, String> {
+    let admin = caller();
+    if !can_approve(&admin) {
+        return Err("Unauthorized: only configured admins can approve withdrawals".to_string());
+    }
+    with_withdrawal_mut(id, |w| {
+        if w.status != WithdrawalStatus::Open {
+            return Err(format!("Withdrawal is {:?}", w.status));
+        }
+        if !w.approvals.contains(&admin) {
+            w.approvals.push(admin);
+        }
+        Ok(())
+    }).ok_or("Withdrawal not found")??;
+    execute_if_approved(id).await
+}
+
+/// Cancel an open withdrawal (admin only). A transfer whose outcome is still
+/// unknown cannot be cancelled.
+#[update]
+pub fn cancel_treasury_withdrawal(id: u64) -> Result<TreasuryWithdrawal, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if WITHDRAWALS_EXECUTING.with(|e| e.borrow().contains(&id)) {
+        return Err("This withdrawal is being executed".to_string());
+    }
+    with_withdrawal_mut(id, |w| {
+        if w.status != WithdrawalStatus::Open {
+            return Err(format!("Withdrawal is {:?}", w.status));
+        }
+        if w.created_at_time.is_some() {
+            return Err("The transfer may already have gone through; approve again to retry it, or resolve it".to_string());
+        }
+        w.status = WithdrawalStatus::Cancelled;
+        Ok(w.clone())
+    }).ok_or("Withdrawal not found")?
+}
+
+/// Resolve a withdrawal whose transfer has unknown outcome by hand after
+/// checking the ledger (admin only): pass the block index if it was executed,
+/// or None to check the balance and send it again on the next approval.
+#[update]
+pub fn resolve_treasury_withdrawal(id: u64, block_index: Option<u64>) -> Result<TreasuryWithdrawal, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    if WITHDRAWALS_EXECUTING.with(|e| e.borrow().contains(&id)) {
+        return Err("This withdrawal is being executed".to_string());
+    }
+    let withdrawal = with_withdrawal_mut(id, |w| {
+        if w.status != WithdrawalStatus::Open || w.created_at_time.is_none() {
+            return Err("The withdrawal has no transfer with unknown outcome".to_string());
+        }
+        match block_index {
+            Some(block_index) => {
+                w.status = WithdrawalStatus::Executed;
+                w.executed_at = Some(time());
+                w.block_index = Some(block_index);
+                w.last_error = None;
+            }
+            None => {
+                w.created_at_time = None;
+                w.last_error = Some("Not executed according to an admin; approve again to retry".to_string());
+            }
+        }
+        Ok(w.clone())
+    }).ok_or("Withdrawal not found")??;
+    if let Some(block_index) = block_index {
+        record_entry(withdrawal.ledger, TreasurySource::Withdrawal(id), withdrawal.amount, Some(block_index));
+    }
+    Ok(withdrawal)
+}
+
+/// All withdrawal proposals, newest first (admin only)
+#[query]
+pub fn get_treasury_withdrawals() -> Result<Vec<TreasuryWithdrawal>, String> {
+    if !crate::is_admin(&caller()) {
+        return Err("Unauthorized: admin only".to_string());
+    }
+    Ok(WITHDRAWALS.with(|w| w.borrow().iter().rev().cloned().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_cut_is_skipped_below_the_ledger_fee() {
+        crate::config::set_platform_fee_bps(250);
+        assert_eq!(platform_cut(1_000_000, 10_000), 25_000);
+        assert_eq!(platform_cut(400_000, 10_000), 0);
+        crate::config::set_platform_fee_bps(0);
+        assert_eq!(platform_cut(1_000_000, 10_000), 0);
+    }
+
+    #[cfg(feature = "mock-ledger")]
+    mod against_the_mock_ledger {
+        use super::*;
+        use crate::config::{CanisterConfig, Environment};
+        use crate::mock_ledger::testing::*;
+
+        const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+        fn user(n: u8) -> Principal {
+            Principal::from_slice(&[n; 29])
+        }
+
+        // Testing environment with user 9 as the only admin
+        fn setup() -> Principal {
+            crate::config::load(CanisterConfig {
+                environment: Environment::Testing,
+                admins: vec![user(9)],
+                ..Default::default()
+            }, None);
+            crate::config::set_platform_fee_bps(0);
+            set_caller(user(9));
+            ledger_canister_id()
+        }
+
+        fn fund_treasury(ledger: Principal, amount: u64) {
+            assert!(crate::mock_ledger::mock_ledger_mint(ledger, treasury_account(), amount).is_ok());
+        }
+
+        #[test]
+        fn nft_payment_is_forwarded_to_the_seller() {
+            let ledger = setup();
+            let fee = token_fee(ledger);
+            mint(ledger, user(1), 2_000_000);
+            set_caller(user(1));
+            assert!(crate::mock_ledger::mock_ledger_approve(ledger, 1_000_000 + fee).is_ok());
+
+            let sale = block_on(collect_nft_payment(7, user(1), user(2), 1_000_000)).unwrap().unwrap();
+            assert!(sale.payout_block.is_some());
+            assert_eq!(balance_of(ledger, Account::from(user(2))), 1_000_000 - fee);
+            assert_eq!(balance_of(ledger, treasury_account()), 0);
+        }
+
+        #[test]
+        fn seller_payout_with_unknown_outcome_is_not_resent_after_the_dedup_window() {
+            let ledger = setup();
+            let fee = token_fee(ledger);
+            fund_treasury(ledger, 1_000_000);
+            NFT_SALES.with(|s| s.borrow_mut().insert(7, NftSale {
+                artwork_id: 7,
+                buyer: user(1),
+                seller: user(2),
+                ledger,
+                price: 1_000_000,
+                platform_fee: 0,
+                seller_amount: 1_000_000 - fee,
+                payment_block: 0,
+                sold_at: time(),
+                payout_created_at: time(),
+                payout_block: None,
+                payout_error: None,
+                payout_unknown: false,
+            }));
+
+            fail_next_calls(1);
+            block_on(pay_seller(7));
+            assert!(get_nft_sale(7).unwrap().payout_unknown);
+
+            advance_time(2 * DAY_NS);
+            block_on(pay_seller(7));
+            let sale = get_nft_sale(7).unwrap();
+            assert!(sale.payout_block.is_none());
+            assert!(sale.payout_error.unwrap().contains("deduplication window"));
+            assert_eq!(balance_of(ledger, Account::from(user(2))), 0);
+
+            // Checked on the ledger: it was not executed, so it goes out as a new transfer
+            assert!(resolve_nft_sale_payout(7, None).is_ok());
+            block_on(pay_seller(7));
+            assert!(get_nft_sale(7).unwrap().payout_block.is_some());
+            assert_eq!(balance_of(ledger, Account::from(user(2))), 1_000_000 - fee);
+        }
+
+        #[test]
+        fn withdrawal_with_unknown_outcome_is_not_resent_after_the_dedup_window() {
+            let ledger = setup();
+            let to = Account::from(user(3));
+            // Without funds the proposal stays open
+            assert!(block_on(propose_treasury_withdrawal(ledger, to, 500_000, "grants".to_string())).is_err());
+            let id = WITHDRAWAL_ID.with(|n| *n.borrow());
+            fund_treasury(ledger, 1_000_000);
+            // As if an attempt had unknown outcome
+            with_withdrawal_mut(id, |w| w.created_at_time = Some(time()));
+
+            advance_time(2 * DAY_NS);
+            let error = block_on(approve_treasury_withdrawal(id)).unwrap_err();
+            assert!(error.contains("deduplication window"));
+            assert_eq!(balance_of(ledger, to), 0);
+            assert!(cancel_treasury_withdrawal(id).is_err());
+
+            assert!(resolve_treasury_withdrawal(id, None).is_ok());
+            let withdrawal = block_on(approve_treasury_withdrawal(id)).unwrap();
+            assert_eq!(withdrawal.status, WithdrawalStatus::Executed);
+            assert_eq!(balance_of(ledger, to), 500_000);
+        }
+    }
+}
@@ -179,7 +179,7 @@ const ArtCardDetail = () => {
           const balance = await opencritique_backend.get_bounty_balance(
             Number(artwork.id)
           );
-          setBountyBalance(Number(balance));
+          setBountyBalance("Ok" in balance ? Number(balance.Ok.e8s) : 0);
           console.log("Current bounty balance:", balance);
         } catch (error) {
           console.error("Error fetching bounty balance:", error);
@@ -355,7 +355,7 @@ const ArtCardDetail = () => {
       // Update bounty balance if bounty was used
       if (bountyBalance > 0) {
         const newBalance = await opencritique_backend.get_bounty_balance(Number(id));
-        setBountyBalance(Number(newBalance));
+        setBountyBalance("Ok" in newBalance ? Number(newBalance.Ok.e8s) : 0);
       }
 
       // Close modal and reset
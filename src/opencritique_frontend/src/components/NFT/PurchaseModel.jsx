@@ -1,8 +1,49 @@
 import React, { useState } from "react";
 import { X, Wallet, CreditCard, Shield } from "lucide-react";
-import { opencritique_backend } from "../../../../declarations/opencritique_backend";
+import { Principal } from "@dfinity/principal";
+import {
+  opencritique_backend,
+  canisterId as backendCanisterId,
+  idlFactory as backendIdlFactory,
+} from "../../../../declarations/opencritique_backend";
 import { useUserContext } from "../context/UserContext";
 
+// Just the ICRC-2 approve call, to let the backend take the price from the buyer
+const ledgerIdlFactory = ({ IDL }) => {
+  const Account = IDL.Record({
+    owner: IDL.Principal,
+    subaccount: IDL.Opt(IDL.Vec(IDL.Nat8)),
+  });
+  const ApproveArgs = IDL.Record({
+    fee: IDL.Opt(IDL.Nat),
+    memo: IDL.Opt(IDL.Vec(IDL.Nat8)),
+    from_subaccount: IDL.Opt(IDL.Vec(IDL.Nat8)),
+    created_at_time: IDL.Opt(IDL.Nat64),
+    amount: IDL.Nat,
+    expected_allowance: IDL.Opt(IDL.Nat),
+    expires_at: IDL.Opt(IDL.Nat64),
+    spender: Account,
+  });
+  const ApproveError = IDL.Variant({
+    GenericError: IDL.Record({ message: IDL.Text, error_code: IDL.Nat }),
+    TemporarilyUnavailable: IDL.Null,
+    Duplicate: IDL.Record({ duplicate_of: IDL.Nat }),
+    BadFee: IDL.Record({ expected_fee: IDL.Nat }),
+    AllowanceChanged: IDL.Record({ current_allowance: IDL.Nat }),
+    CreatedInFuture: IDL.Record({ ledger_time: IDL.Nat64 }),
+    TooOld: IDL.Null,
+    Expired: IDL.Record({ ledger_time: IDL.Nat64 }),
+    InsufficientFunds: IDL.Record({ balance: IDL.Nat }),
+  });
+  return IDL.Service({
+    icrc2_approve: IDL.Func(
+      [ApproveArgs],
+      [IDL.Variant({ Ok: IDL.Nat, Err: ApproveError })],
+      []
+    ),
+  });
+};
+
 const PurchaseModal = ({ artwork, isOpen, onClose, onPurchaseSuccess }) => {
   const [currentStep, setCurrentStep] = useState(1);
   const [paymentMethod, setPaymentMethod] = useState("wallet");
@@ -35,11 +76,43 @@ const PurchaseModal = ({ artwork, isOpen, onClose, onPurchaseSuccess }) => {
     setError("");
 
     try {
-      // Step 1: Validate payment (mock for now)
-      await new Promise((resolve) => setTimeout(resolve, 1500));
+      setCurrentStep(2);
+
+      // Step 1: Approve the price plus the transfer fee on the ICP ledger.
+      // The backend collects it with icrc2_transfer_from, so the approval
+      // and the purchase must both come from the buyer's wallet.
+      const config = await opencritique_backend.get_canister_config();
+      const price = BigInt(artwork.nft_price || 0);
+      if (price > 0n) {
+        const ledger = await window.ic.plug.createActor({
+          canisterId: config.ledger_canister_id.toText(),
+          interfaceFactory: ledgerIdlFactory,
+        });
+        const approval = await ledger.icrc2_approve({
+          fee: [],
+          memo: [],
+          from_subaccount: [],
+          created_at_time: [],
+          amount: price + config.ledger_fee,
+          expected_allowance: [],
+          expires_at: [],
+          spender: {
+            owner: Principal.fromText(backendCanisterId),
+            subaccount: [],
+          },
+        });
+        if (approval.Err) {
+          throw new Error(
+            `Approval failed: ${Object.keys(approval.Err)[0]}`
+          );
+        }
+      }
 
       // Step 2: Set NFT buyer in backend
-      setCurrentStep(2);
+      const backend = await window.ic.plug.createActor({
+        canisterId: backendCanisterId,
+        interfaceFactory: backendIdlFactory,
+      });
 
     //   console.log("Artwork ID:", Number(artwork.id));
     //   console.log("Principal Object:", principalObj);
@@ -50,9 +123,7 @@ const PurchaseModal = ({ artwork, isOpen, onClose, onPurchaseSuccess }) => {
     //   );
 
       // Only pass the artwork ID - backend will use caller() as buyer
-      const result = await opencritique_backend.set_nft_buyer(
-        Number(artwork.id)
-      );
+      const result = await backend.set_nft_buyer(BigInt(artwork.id));
 
       if (result.Err) {
         throw new Error(result.Err);
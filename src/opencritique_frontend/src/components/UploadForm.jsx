@@ -110,7 +110,11 @@ const UploadForm = () => {
 
       // Verify the bounty balance
       const balance = await opencritique_backend.get_bounty_balance(artworkId);
-      console.log("Bounty balance after funding:", balance);
+      if ("Ok" in balance) {
+        console.log("Bounty balance after funding:", Number(balance.Ok.e8s), "e8s");
+      } else {
+        console.warn("Bounty balance unavailable:", balance.Err);
+      }
 
       return transferResult;
     } catch (error) {